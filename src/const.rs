@@ -1,39 +1,190 @@
 // all of keywords
-pub const COMMENT: &'static str = "//";
-pub const PENUP: &'static str = "PENUP";
-pub const PENDOWN: &'static str = "PENDOWN";
-pub const FORWARD: &'static str = "FORWARD";
-pub const BACK: &'static str = "BACK";
-pub const LEFT: &'static str = "LEFT";
-pub const RIGHT: &'static str = "RIGHT";
-pub const SETX: &'static str = "SETX";
-pub const SETY: &'static str = "SETY";
-pub const SETHEADING: &'static str = "SETHEADING";
-pub const TURN: &'static str = "TURN";
-pub const SETPENCOLOR: &'static str = "SETPENCOLOR";
-pub const XCOR: &'static str = "XCOR";
-pub const YCOR: &'static str = "YCOR";
-pub const HEADING: &'static str = "HEADING";
-pub const COLOR: &'static str = "COLOR";
-pub const MAKE: &'static str = "MAKE";
-pub const ADDASSIGN: &'static str = "ADDASSIGN";
-pub const IF: &'static str = "IF";
-pub const WHILE: &'static str = "WHILE";
-pub const TO: &'static str = "TO";
-pub const END: &'static str = "END";
+pub const COMMENT: &str = "//";
+pub const PENUP: &str = "PENUP";
+pub const PENDOWN: &str = "PENDOWN";
+pub const PENPAINT: &str = "PENPAINT";
+pub const PENERASE: &str = "PENERASE";
+pub const PENREVERSE: &str = "PENREVERSE";
+pub const FORWARD: &str = "FORWARD";
+pub const BACK: &str = "BACK";
+pub const LEFT: &str = "LEFT";
+pub const RIGHT: &str = "RIGHT";
+pub const SETX: &str = "SETX";
+pub const SETY: &str = "SETY";
+pub const SETXY: &str = "SETXY";
+pub const SETPOS: &str = "SETPOS";
+pub const HOME: &str = "HOME";
+pub const CLEARSCREEN: &str = "CLEARSCREEN";
+pub const CLEAN: &str = "CLEAN";
+pub const WRAP: &str = "WRAP";
+pub const WINDOW: &str = "WINDOW";
+pub const FENCE: &str = "FENCE";
+pub const SETHEADING: &str = "SETHEADING";
+pub const TURN: &str = "TURN";
+pub const ARC: &str = "ARC";
+pub const CIRCLE: &str = "CIRCLE";
+pub const SETPENCOLOR: &str = "SETPENCOLOR";
+pub const SETPENSIZE: &str = "SETPENSIZE";
+pub const SETPENPATTERN: &str = "SETPENPATTERN";
+pub const SETLINECAP: &str = "SETLINECAP";
+pub const SETLINEJOIN: &str = "SETLINEJOIN";
+pub const SETPALETTE: &str = "SETPALETTE";
+pub const SETBACKGROUND: &str = "SETBACKGROUND";
+pub const LABEL: &str = "LABEL";
+pub const SETLABELHEIGHT: &str = "SETLABELHEIGHT";
+pub const SETLABELFONT: &str = "SETLABELFONT";
+pub const SETFILLCOLOR: &str = "SETFILLCOLOR";
+pub const BEGINFILL: &str = "BEGINFILL";
+pub const ENDFILL: &str = "ENDFILL";
+pub const FILL: &str = "FILL";
+pub const NEWTURTLE: &str = "NEWTURTLE";
+pub const TELL: &str = "TELL";
+pub const ASK: &str = "ASK";
+pub const PUSHTURTLE: &str = "PUSHTURTLE";
+pub const POPTURTLE: &str = "POPTURTLE";
+pub const SHOWTURTLE: &str = "SHOWTURTLE";
+pub const HIDETURTLE: &str = "HIDETURTLE";
+pub const STAMP: &str = "STAMP";
+pub const SETSHAPE: &str = "SETSHAPE";
+pub const SETSCALE: &str = "SETSCALE";
+pub const TRANSLATE: &str = "TRANSLATE";
+pub const ROTATE: &str = "ROTATE";
+pub const MIRROR: &str = "MIRROR";
+pub const PUSHTRANSFORM: &str = "PUSHTRANSFORM";
+pub const POPTRANSFORM: &str = "POPTRANSFORM";
+pub const XCOR: &str = "XCOR";
+pub const YCOR: &str = "YCOR";
+pub const HEADING: &str = "HEADING";
+pub const POS: &str = "POS";
+pub const COLOR: &str = "COLOR";
+pub const PENCOLOR: &str = "PENCOLOR";
+pub const PALETTE: &str = "PALETTE";
+pub const BACKGROUND: &str = "BACKGROUND";
+pub const WHO: &str = "WHO";
+pub const PENDOWNP: &str = "PENDOWNP";
+pub const PENSIZE: &str = "PENSIZE";
+pub const SHOWNP: &str = "SHOWNP";
+pub const TOWARDS: &str = "TOWARDS";
+pub const DISTANCE: &str = "DISTANCE";
+pub const CANVASWIDTH: &str = "CANVASWIDTH";
+pub const CANVASHEIGHT: &str = "CANVASHEIGHT";
+pub const BOUNDS: &str = "BOUNDS";
+pub const MAKE: &str = "MAKE";
+pub const ADDASSIGN: &str = "ADDASSIGN";
+pub const SUBASSIGN: &str = "SUBASSIGN";
+pub const MULASSIGN: &str = "MULASSIGN";
+pub const DIVASSIGN: &str = "DIVASSIGN";
+pub const IF: &str = "IF";
+pub const WHILE: &str = "WHILE";
+pub const TO: &str = "TO";
+pub const END: &str = "END";
+
+// list delimiters
+pub const LIST_START: &str = "[";
+pub const LIST_END: &str = "]";
 
 // operation code
-pub const PLUS: &'static str = "+";
-pub const MINUS: &'static str = "-";
-pub const TIMES: &'static str = "*";
-pub const DIVIDE: &'static str = "/";
-pub const EQ: &'static str = "EQ";
-pub const NE: &'static str = "NE";
-pub const LT: &'static str = "LT";
-pub const GT: &'static str = "GT";
-pub const AND: &'static str = "AND";
-pub const OR: &'static str = "OR";
+pub const PLUS: &str = "+";
+pub const MINUS: &str = "-";
+pub const TIMES: &str = "*";
+pub const DIVIDE: &str = "/";
+pub const EQ: &str = "EQ";
+pub const NE: &str = "NE";
+pub const LT: &str = "LT";
+pub const GT: &str = "GT";
+pub const AND: &str = "AND";
+pub const OR: &str = "OR";
 
 // boolean
-pub const TRUE: &'static str = "TRUE";
-pub const FALSE: &'static str = "FALSE";
+pub const TRUE: &str = "TRUE";
+pub const FALSE: &str = "FALSE";
+
+// names which can't be used for procedures or their inputs
+pub const KEYWORDS: &[&str] = &[
+    PENUP,
+    PENDOWN,
+    PENPAINT,
+    PENERASE,
+    PENREVERSE,
+    FORWARD,
+    BACK,
+    LEFT,
+    RIGHT,
+    SETX,
+    SETY,
+    SETXY,
+    SETPOS,
+    HOME,
+    CLEARSCREEN,
+    CLEAN,
+    WRAP,
+    WINDOW,
+    FENCE,
+    SETHEADING,
+    TURN,
+    ARC,
+    CIRCLE,
+    SETPENCOLOR,
+    SETPENSIZE,
+    SETPENPATTERN,
+    SETLINECAP,
+    SETLINEJOIN,
+    SETPALETTE,
+    SETBACKGROUND,
+    LABEL,
+    SETLABELHEIGHT,
+    SETLABELFONT,
+    SETFILLCOLOR,
+    BEGINFILL,
+    ENDFILL,
+    FILL,
+    NEWTURTLE,
+    TELL,
+    ASK,
+    PUSHTURTLE,
+    POPTURTLE,
+    SHOWTURTLE,
+    HIDETURTLE,
+    STAMP,
+    SETSHAPE,
+    SETSCALE,
+    TRANSLATE,
+    ROTATE,
+    MIRROR,
+    PUSHTRANSFORM,
+    POPTRANSFORM,
+    XCOR,
+    YCOR,
+    HEADING,
+    POS,
+    COLOR,
+    PENCOLOR,
+    PALETTE,
+    BACKGROUND,
+    WHO,
+    PENDOWNP,
+    PENSIZE,
+    SHOWNP,
+    TOWARDS,
+    DISTANCE,
+    CANVASWIDTH,
+    CANVASHEIGHT,
+    BOUNDS,
+    MAKE,
+    ADDASSIGN,
+    SUBASSIGN,
+    MULASSIGN,
+    DIVASSIGN,
+    IF,
+    WHILE,
+    TO,
+    END,
+    EQ,
+    NE,
+    LT,
+    GT,
+    AND,
+    OR,
+    TRUE,
+    FALSE,
+];
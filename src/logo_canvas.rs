@@ -0,0 +1,1078 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::path::Path;
+
+use tiny_skia::{
+    BlendMode, ColorU8, FillRule, LineCap, LineJoin, Mask, Paint, PathBuilder, Pixmap, PixmapPaint,
+    PremultipliedColorU8, Rect, Stroke, StrokeDash, Transform,
+};
+
+use crate::{logo_color::LogoColor, logo_font};
+
+/// The drawing surface of the runner.
+/// Every stroke is rasterized and also kept as an svg element, so both output formats
+/// see exactly the same drawing. Framed and animated output is drawn again on a canvas
+/// of its own from the runner's display list.
+/// The background is only put below the drawing when saving, so it can change any time.
+pub struct LogoCanvas {
+    width: u32,
+    height: u32,
+    pixmap: Pixmap,
+    elements: Vec<String>,
+    // svg masks hiding what was erased, each covers every element drawn before it
+    erase_masks: Vec<Vec<String>>,
+    // erasing again right after erasing adds to the last mask
+    erasing: bool,
+    background: LogoColor,
+    // lines drawn while a fill is open go to their own layer, so the fill ends up below them
+    fill_layer: Option<FillLayer>,
+    // the area covered by everything drawn, including what is off the canvas
+    bounds: Option<Rect>,
+    // from drawing coordinates to pixels, only set when drawing again at another scale
+    transform: Transform,
+    animation: Option<Animation>,
+    // seconds into the animation the next element starts
+    clock: f32,
+    // the turtle drawing now, its glyph follows what it draws in the animation
+    turtle: usize,
+    // the animated turtles, one glyph per turtle with everything it does over time
+    glyphs: Vec<Vec<String>>,
+}
+
+/// Makes the svg draw itself in the order the program drew.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Animation {
+    /// pixels of line drawn per second
+    pub speed: f32,
+    /// show every turtle moving along the lines as they are drawn and where it goes between them
+    pub turtle: bool,
+}
+
+/// A line the animation draws over time.
+struct Trace {
+    // svg path data of the line
+    d: String,
+    length: f32,
+    color: LogoColor,
+    // patterned lines already use the dash array, so they show up all at once
+    solid: bool,
+}
+
+/// When an element of the animated svg shows up.
+#[derive(Clone, Copy)]
+struct Timing {
+    begin: f32,
+    duration: f32,
+    solid: bool,
+}
+
+struct FillLayer {
+    // where the fill element goes in the svg
+    element_index: usize,
+    pixmap: Pixmap,
+}
+
+/// How the pen strokes a line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PenStyle {
+    pub width: u32,
+    pub pattern: PenPattern,
+    pub cap: LineCap,
+    pub join: LineJoin,
+    pub mode: PenMode,
+}
+
+/// What the pen does to the pixels below it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PenMode {
+    Paint,
+    /// draw in the background color
+    Erase,
+    /// invert the pen color bits below, drawing twice restores the drawing
+    Reverse,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PenPattern {
+    Solid,
+    Dashed,
+    Dotted,
+    DashDot,
+}
+
+/// How LABEL draws text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelStyle {
+    pub height: u32,
+    pub font: String,
+}
+
+impl Default for LabelStyle {
+    fn default() -> Self {
+        LabelStyle {
+            height: 12,
+            font: "sans-serif".to_string(),
+        }
+    }
+}
+
+impl Default for PenStyle {
+    fn default() -> Self {
+        PenStyle {
+            width: 1,
+            pattern: PenPattern::Solid,
+            cap: LineCap::Butt,
+            join: LineJoin::Miter,
+            mode: PenMode::Paint,
+        }
+    }
+}
+
+impl PenStyle {
+    /// Dash lengths in pixels, scaled with the pen width.
+    fn dash_array(&self) -> Vec<u32> {
+        let w = self.width.max(1);
+        match self.pattern {
+            PenPattern::Solid => vec![],
+            PenPattern::Dashed => vec![4 * w, 3 * w],
+            PenPattern::Dotted => vec![w, 2 * w],
+            PenPattern::DashDot => vec![4 * w, 2 * w, w, 2 * w],
+        }
+    }
+
+    fn stroke(&self) -> Stroke {
+        let dash = self.dash_array();
+        Stroke {
+            width: self.width as f32,
+            line_cap: self.cap,
+            line_join: self.join,
+            dash: StrokeDash::new(dash.iter().map(|d| *d as f32).collect(), 0.0),
+            ..Stroke::default()
+        }
+    }
+
+    /// Stroke attributes which differ from the svg defaults.
+    fn svg_attributes(&self) -> String {
+        let mut attributes = String::new();
+        if self.width != 1 {
+            attributes.push_str(&format!(" stroke-width=\"{}\"", self.width));
+        }
+        match self.cap {
+            LineCap::Butt => {}
+            LineCap::Round => attributes.push_str(" stroke-linecap=\"round\""),
+            LineCap::Square => attributes.push_str(" stroke-linecap=\"square\""),
+        }
+        match self.join {
+            LineJoin::Miter => {}
+            LineJoin::Round => attributes.push_str(" stroke-linejoin=\"round\""),
+            LineJoin::Bevel => attributes.push_str(" stroke-linejoin=\"bevel\""),
+        }
+        let dash = self.dash_array();
+        if !dash.is_empty() {
+            let dash = dash.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+            attributes.push_str(&format!(" stroke-dasharray=\"{}\"", dash.join(" ")));
+        }
+        attributes
+    }
+}
+
+/// A turtle arc, curving right around a center beside the turtle for positive angles
+/// and left for negative ones. Angles are in radians from the positive x axis, clockwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TurtleArc {
+    center_x: f32,
+    center_y: f32,
+    radius: f32,
+    start: f32,
+    sweep: f32,
+}
+
+impl TurtleArc {
+    pub fn new(x: i32, y: i32, direction: i32, angle: i32, radius: i32) -> Self {
+        let radius = radius as f32;
+        let side = if angle < 0 { -1.0 } else { 1.0 };
+        let (sin, cos) = (direction as f32).to_radians().sin_cos();
+        let start = (direction as f32).to_radians() + if angle < 0 { 0.0 } else { PI };
+        TurtleArc {
+            center_x: x as f32 + side * radius * cos,
+            center_y: y as f32 + side * radius * sin,
+            radius,
+            start,
+            sweep: (angle as f32).to_radians(),
+        }
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (self.center_x, self.center_y)
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// The turn in degrees, positive is clockwise on the canvas.
+    pub fn sweep(&self) -> f32 {
+        self.sweep.to_degrees()
+    }
+
+    fn point_at(&self, angle: f32) -> (f32, f32) {
+        let (sin, cos) = angle.sin_cos();
+        (
+            self.center_x + self.radius * cos,
+            self.center_y + self.radius * sin,
+        )
+    }
+
+    pub fn end(&self) -> (i32, i32) {
+        let (x, y) = self.point_at(self.start + self.sweep);
+        (x.round() as i32, y.round() as i32)
+    }
+
+    /// Points along the arc at most `step` degrees apart, the start point excluded.
+    pub fn points(&self, step: f32) -> Vec<(i32, i32)> {
+        let count = (self.sweep.abs().to_degrees() / step).ceil().max(1.0) as usize;
+        (1..=count)
+            .map(|i| {
+                let (x, y) = self.point_at(self.start + self.sweep * i as f32 / count as f32);
+                (x.round() as i32, y.round() as i32)
+            })
+            .collect()
+    }
+
+    /// The arc as cubic curves of at most a quarter turn each.
+    fn path(&self) -> Option<tiny_skia::Path> {
+        let count = (self.sweep.abs() / FRAC_PI_2).ceil().max(1.0) as usize;
+        let delta = self.sweep / count as f32;
+        let k = 4.0 / 3.0 * (delta / 4.0).tan() * self.radius;
+        let mut path = PathBuilder::new();
+        let (x, y) = self.point_at(self.start);
+        path.move_to(x, y);
+        for i in 0..count {
+            let from = self.start + delta * i as f32;
+            let to = from + delta;
+            let (x0, y0) = self.point_at(from);
+            let (x1, y1) = self.point_at(to);
+            path.cubic_to(
+                x0 - k * from.sin(),
+                y0 + k * from.cos(),
+                x1 + k * to.sin(),
+                y1 - k * to.cos(),
+                x1,
+                y1,
+            );
+        }
+        path.finish()
+    }
+
+    /// A full turn is a circle element, anything else an arc ending on the rounded end point.
+    fn svg_element(&self, x: i32, y: i32, stroke: &str) -> String {
+        if self.sweep.abs() >= 2.0 * PI {
+            return format!(
+                "<circle fill=\"none\" {} cx=\"{}\" cy=\"{}\" r=\"{}\"/>",
+                stroke,
+                svg_number(self.center_x),
+                svg_number(self.center_y),
+                svg_number(self.radius)
+            );
+        }
+        format!(
+            "<path fill=\"none\" {} d=\"{}\"/>",
+            stroke,
+            self.svg_path(x, y)
+        )
+    }
+
+    /// The arc as path data from its starting point, a full turn as two half turns
+    /// so that it starts where the turtle was.
+    fn svg_path(&self, x: i32, y: i32) -> String {
+        let (r, clockwise) = (svg_number(self.radius), (self.sweep > 0.0) as u8);
+        if self.sweep.abs() >= 2.0 * PI {
+            let (half_x, half_y) = self.point_at(self.start + PI);
+            return format!(
+                "M {x} {y} A {r} {r} 0 0 {clockwise} {} {} A {r} {r} 0 0 {clockwise} {x} {y}",
+                svg_number(half_x),
+                svg_number(half_y)
+            );
+        }
+        let (end_x, end_y) = self.end();
+        format!(
+            "M {} {} A {r} {r} 0 {} {} {} {}",
+            x,
+            y,
+            (self.sweep.abs() > PI) as u8,
+            clockwise,
+            end_x,
+            end_y
+        )
+    }
+}
+
+/// Hide an element of the animated svg until its time comes, lines are drawn in
+/// along their length.
+fn animated(element: String, timing: Option<Timing>) -> String {
+    let Some(Timing {
+        begin,
+        duration,
+        solid,
+    }) = timing
+    else {
+        return element;
+    };
+    let (begin, duration) = (svg_number(begin), svg_number(duration));
+    let element = match element.strip_suffix("/>") {
+        Some(start) if solid => format!(
+            "{start} pathLength=\"1\" stroke-dasharray=\"1\" stroke-dashoffset=\"1\"><animate attributeName=\"stroke-dashoffset\" to=\"0\" begin=\"{begin}s\" dur=\"{duration}s\" fill=\"freeze\"/></path>"
+        ),
+        _ => element,
+    };
+    format!(
+        "<g visibility=\"hidden\"><set attributeName=\"visibility\" to=\"visible\" begin=\"{begin}s\" fill=\"freeze\"/>{element}</g>"
+    )
+}
+
+fn svg_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn svg_number(num: f32) -> String {
+    let num = format!("{:.3}", num);
+    match num.trim_end_matches('0').trim_end_matches('.') {
+        "-0" => "0".to_string(),
+        num => num.to_string(),
+    }
+}
+
+impl PenPattern {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "solid" => Ok(PenPattern::Solid),
+            "dashed" => Ok(PenPattern::Dashed),
+            "dotted" => Ok(PenPattern::Dotted),
+            "dashdot" => Ok(PenPattern::DashDot),
+            _ => Err(format!("invalid pen pattern: {}", name)),
+        }
+    }
+}
+
+pub fn parse_line_cap(name: &str) -> Result<LineCap, String> {
+    match name.to_lowercase().as_str() {
+        "butt" => Ok(LineCap::Butt),
+        "round" => Ok(LineCap::Round),
+        "square" => Ok(LineCap::Square),
+        _ => Err(format!("invalid line cap: {}", name)),
+    }
+}
+
+pub fn parse_line_join(name: &str) -> Result<LineJoin, String> {
+    match name.to_lowercase().as_str() {
+        "miter" => Ok(LineJoin::Miter),
+        "round" => Ok(LineJoin::Round),
+        "bevel" => Ok(LineJoin::Bevel),
+        _ => Err(format!("invalid line join: {}", name)),
+    }
+}
+
+/// Fill color attributes, the opacity is left out for opaque colors.
+fn svg_fill(color: LogoColor) -> String {
+    if color.alpha == 255 {
+        format!("fill=\"{}\"", color.to_hex())
+    } else {
+        format!(
+            "fill=\"{}\" fill-opacity=\"{}\"",
+            color.to_hex(),
+            svg_opacity(color.alpha)
+        )
+    }
+}
+
+/// Stroke color attributes, the opacity is left out for opaque colors.
+fn svg_stroke(color: LogoColor) -> String {
+    if color.alpha == 255 {
+        format!("stroke=\"{}\"", color.to_hex())
+    } else {
+        format!(
+            "stroke=\"{}\" stroke-opacity=\"{}\"",
+            color.to_hex(),
+            svg_opacity(color.alpha)
+        )
+    }
+}
+
+fn svg_opacity(alpha: u8) -> String {
+    svg_number(alpha as f32 / 255.0)
+}
+
+// erased parts are black in the svg masks
+const ERASED: LogoColor = LogoColor {
+    red: 0,
+    green: 0,
+    blue: 0,
+    alpha: 255,
+};
+
+/// Paint clearing the drawing to let the background through.
+fn eraser_paint() -> Paint<'static> {
+    Paint {
+        blend_mode: BlendMode::Clear,
+        anti_alias: true,
+        ..Paint::default()
+    }
+}
+
+fn skia_paint(color: LogoColor) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(color.red, color.green, color.blue, color.alpha);
+    paint.anti_alias = true;
+    paint
+}
+
+impl LogoCanvas {
+    pub fn new(width: u32, height: u32) -> Result<Self, String> {
+        let mut canvas = LogoCanvas {
+            width,
+            height,
+            pixmap: Pixmap::new(width, height).ok_or("invalid canvas size")?,
+            elements: Vec::new(),
+            erase_masks: Vec::new(),
+            erasing: false,
+            background: LogoColor::rgb(0, 0, 0),
+            fill_layer: None,
+            bounds: None,
+            transform: Transform::identity(),
+            animation: None,
+            clock: 0.0,
+            turtle: 0,
+            glyphs: Vec::new(),
+        };
+        canvas.clear();
+        Ok(canvas)
+    }
+
+    /// Erase everything drawn, an open fill stays open.
+    pub fn clear(&mut self) {
+        self.pixmap.fill(tiny_skia::Color::TRANSPARENT);
+        self.elements.clear();
+        self.erase_masks.clear();
+        self.erasing = false;
+        self.bounds = None;
+        self.clock = 0.0;
+        self.glyphs.clear();
+        if let Some(layer) = self.fill_layer.as_mut() {
+            layer.pixmap.fill(tiny_skia::Color::TRANSPARENT);
+            layer.element_index = self.elements.len();
+        }
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn with_animation(mut self, animation: Animation) -> Self {
+        self.animation = Some(animation);
+        self
+    }
+
+    pub fn set_background(&mut self, color: LogoColor) {
+        self.background = color;
+    }
+
+    pub fn get_background(&self) -> LogoColor {
+        self.background
+    }
+
+    /// The area covered by the drawing, `None` when nothing has been drawn.
+    pub fn bounds(&self) -> Option<Rect> {
+        self.bounds
+    }
+
+    fn extend_bounds(&mut self, rect: Rect) {
+        self.bounds = match self.bounds {
+            Some(bounds) => Rect::from_ltrb(
+                bounds.left().min(rect.left()),
+                bounds.top().min(rect.top()),
+                bounds.right().max(rect.right()),
+                bounds.bottom().max(rect.bottom()),
+            ),
+            None => Some(rect),
+        };
+    }
+
+    fn target(&mut self) -> &mut Pixmap {
+        match self.fill_layer.as_mut() {
+            Some(layer) => &mut layer.pixmap,
+            None => &mut self.pixmap,
+        }
+    }
+
+    /// Stroke a path with the pen.
+    fn stroke_path(&mut self, path: &tiny_skia::Path, color: LogoColor, style: &PenStyle) {
+        let (stroke, transform) = (style.stroke(), self.transform);
+        if style.mode != PenMode::Erase {
+            if let Some(outline) = path.stroke(&stroke, 1.0) {
+                self.extend_bounds(outline.bounds());
+            }
+        }
+        match style.mode {
+            PenMode::Paint => {
+                self.target()
+                    .stroke_path(path, &skia_paint(color), &stroke, transform, None)
+            }
+            PenMode::Erase => {
+                let eraser = eraser_paint();
+                self.target()
+                    .stroke_path(path, &eraser, &stroke, transform, None);
+                // what is below an open fill is erased as well
+                if self.fill_layer.is_some() {
+                    self.pixmap
+                        .stroke_path(path, &eraser, &stroke, transform, None);
+                }
+            }
+            PenMode::Reverse => {
+                if let Some(outline) = path.stroke(&stroke, 1.0) {
+                    self.reverse_area(&outline, transform, color);
+                }
+            }
+        }
+    }
+
+    /// Fill a path with the pen.
+    fn fill_pen_path(
+        &mut self,
+        path: &tiny_skia::Path,
+        transform: Transform,
+        color: LogoColor,
+        mode: PenMode,
+    ) {
+        if mode != PenMode::Erase {
+            if let Some(path) = path.clone().transform(transform) {
+                self.extend_bounds(path.bounds());
+            }
+        }
+        let transform = self.transform.pre_concat(transform);
+        let paint = match mode {
+            PenMode::Paint => skia_paint(color),
+            PenMode::Erase => {
+                if self.fill_layer.is_some() {
+                    self.pixmap.fill_path(
+                        path,
+                        &eraser_paint(),
+                        FillRule::Winding,
+                        transform,
+                        None,
+                    );
+                }
+                eraser_paint()
+            }
+            PenMode::Reverse => return self.reverse_area(path, transform, color),
+        };
+        self.target()
+            .fill_path(path, &paint, FillRule::Winding, transform, None);
+    }
+
+    /// Xor the visible pixels covered by a path with a color. This works on whole pixels
+    /// without anti-aliasing, so that covering the same area again restores it.
+    /// While a fill is open this works on the fill layer, which ends up above the fill.
+    fn reverse_area(&mut self, path: &tiny_skia::Path, transform: Transform, color: LogoColor) {
+        let Some(mut mask) = Mask::new(self.width, self.height) else {
+            return;
+        };
+        mask.fill_path(path, FillRule::Winding, false, transform);
+        let background = self.background;
+        for (pixel, covered) in self.target().pixels_mut().iter_mut().zip(mask.data()) {
+            if *covered == 0 {
+                continue;
+            }
+            // the background shows through where the drawing isn't opaque
+            let below = 255 - pixel.alpha() as u32;
+            let over = |drawn: u8, background: u8| {
+                (drawn as u32 + background as u32 * below / 255).min(255) as u8
+            };
+            let background = ColorU8::from_rgba(
+                background.red,
+                background.green,
+                background.blue,
+                background.alpha,
+            )
+            .premultiply();
+            let visible = PremultipliedColorU8::from_rgba(
+                over(pixel.red(), background.red()),
+                over(pixel.green(), background.green()),
+                over(pixel.blue(), background.blue()),
+                over(pixel.alpha(), background.alpha()),
+            )
+            .unwrap_or(*pixel);
+            let c = visible.demultiply();
+            *pixel = ColorU8::from_rgba(
+                c.red() ^ color.red,
+                c.green() ^ color.green,
+                c.blue() ^ color.blue,
+                c.alpha(),
+            )
+            .premultiply();
+        }
+    }
+
+    /// Keep a pen drawing in the svg, `element` builds it from its paint attributes.
+    /// The reverse mode is approximated with blending, erasing can't just paint the
+    /// background since it may change or be transparent, so it masks what is below.
+    fn push_pen_element(
+        &mut self,
+        mode: PenMode,
+        paint: String,
+        eraser: String,
+        trace: Option<Trace>,
+        element: impl Fn(&str) -> String,
+    ) {
+        let timing = self.advance(trace);
+        let element = |attributes: &str| animated(element(attributes), timing);
+        match mode {
+            PenMode::Paint => self.push_element(element(&paint)),
+            PenMode::Reverse => self.push_element(element(&format!(
+                "{} style=\"mix-blend-mode:difference\"",
+                paint
+            ))),
+            PenMode::Erase => {
+                if !self.erasing {
+                    self.elements.insert(
+                        0,
+                        format!("<g mask=\"url(#erase{})\">", self.erase_masks.len()),
+                    );
+                    self.elements.push("</g>".to_string());
+                    if let Some(layer) = self.fill_layer.as_mut() {
+                        layer.element_index += 1;
+                    }
+                    self.erase_masks.push(Vec::new());
+                    self.erasing = true;
+                }
+                if let Some(mask) = self.erase_masks.last_mut() {
+                    mask.push(element(&eraser));
+                }
+            }
+        }
+    }
+
+    fn push_element(&mut self, element: String) {
+        self.elements.push(element);
+        self.erasing = false;
+    }
+
+    /// Time the next element of the animation, moving the animated turtle along its line.
+    fn advance(&mut self, trace: Option<Trace>) -> Option<Timing> {
+        let animation = self.animation?;
+        let begin = self.clock;
+        let Some(trace) = trace else {
+            return Some(Timing {
+                begin,
+                duration: 0.0,
+                solid: false,
+            });
+        };
+        let duration = trace.length / animation.speed;
+        if animation.turtle && duration > 0.0 {
+            let glyph = self.glyph(begin);
+            glyph.push(format!(
+                "<set attributeName=\"stroke\" to=\"{}\" begin=\"{}s\" fill=\"freeze\"/>",
+                trace.color.to_hex(),
+                svg_number(begin)
+            ));
+            glyph.push(format!(
+                "<animateMotion path=\"{}\" rotate=\"auto\" begin=\"{}s\" dur=\"{}s\" fill=\"freeze\"/>",
+                trace.d,
+                svg_number(begin),
+                svg_number(duration)
+            ));
+        }
+        self.clock += duration;
+        Some(Timing {
+            begin,
+            duration,
+            solid: trace.solid && duration > 0.0,
+        })
+    }
+
+    /// The motions of the animated turtle drawing now, it shows up with its first one.
+    fn glyph(&mut self, begin: f32) -> &mut Vec<String> {
+        if self.glyphs.len() <= self.turtle {
+            self.glyphs.resize(self.turtle + 1, Vec::new());
+        }
+        let glyph = &mut self.glyphs[self.turtle];
+        if glyph.is_empty() {
+            glyph.push(format!(
+                "<set attributeName=\"visibility\" to=\"visible\" begin=\"{}s\" fill=\"freeze\"/>",
+                svg_number(begin)
+            ));
+        }
+        glyph
+    }
+
+    /// Pick the turtle whose glyph follows what is drawn next.
+    pub fn set_turtle(&mut self, turtle: usize) {
+        self.turtle = turtle;
+    }
+
+    /// Move the animated turtle without drawing, taking as long as drawing the line would.
+    /// After a turn it points along the heading until it moves again.
+    pub fn move_turtle(&mut self, from: (f32, f32), to: (f32, f32), heading: i32) {
+        let Some(animation) = self.animation.filter(|animation| animation.turtle) else {
+            return;
+        };
+        let begin = self.clock;
+        let duration = (to.0 - from.0).hypot(to.1 - from.1) / animation.speed;
+        // the glyph points right, a heading of 0 points up
+        let rotate = heading - 90;
+        let motion = if duration > 0.0 {
+            format!(
+                "<animateMotion path=\"M {} {} L {} {}\" rotate=\"{}\" begin=\"{}s\" dur=\"{}s\" fill=\"freeze\"/>",
+                svg_number(from.0),
+                svg_number(from.1),
+                svg_number(to.0),
+                svg_number(to.1),
+                rotate,
+                svg_number(begin),
+                svg_number(duration)
+            )
+        } else {
+            // without a duration it lasts until a later motion takes over
+            format!(
+                "<animateMotion path=\"M {} {}\" rotate=\"{}\" begin=\"{}s\"/>",
+                svg_number(to.0),
+                svg_number(to.1),
+                rotate,
+                svg_number(begin)
+            )
+        };
+        self.glyph(begin).push(motion);
+        self.clock += duration;
+    }
+
+    /// Draw an arc from a starting point.
+    pub fn draw_arc(
+        &mut self,
+        x: i32,
+        y: i32,
+        arc: &TurtleArc,
+        color: LogoColor,
+        style: &PenStyle,
+    ) {
+        if let Some(path) = arc.path() {
+            self.stroke_path(&path, color, style);
+        }
+        let animated = self.animation.is_some();
+        let trace = Trace {
+            d: arc.svg_path(x, y),
+            length: arc.radius * arc.sweep.abs(),
+            color,
+            solid: style.pattern == PenPattern::Solid,
+        };
+        self.push_pen_element(
+            style.mode,
+            format!("{}{}", svg_stroke(color), style.svg_attributes()),
+            format!("{}{}", svg_stroke(ERASED), style.svg_attributes()),
+            Some(trace),
+            |stroke| {
+                // a circle element would start drawing on its right
+                if animated {
+                    format!(
+                        "<path fill=\"none\" {} d=\"{}\"/>",
+                        stroke,
+                        arc.svg_path(x, y)
+                    )
+                } else {
+                    arc.svg_element(x, y, stroke)
+                }
+            },
+        );
+    }
+
+    /// Draw text with its baseline starting at a point and running along the direction.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_label(
+        &mut self,
+        x: i32,
+        y: i32,
+        direction: i32,
+        text: &str,
+        color: LogoColor,
+        mode: PenMode,
+        style: &LabelStyle,
+    ) {
+        // text runs to the right at a heading of 90 degrees
+        let rotation = (direction - 90) % 360;
+        if let Some(path) = logo_font::text_path(text, style.height as f32) {
+            let transform =
+                Transform::from_translate(x as f32, y as f32).pre_rotate(rotation as f32);
+            self.fill_pen_path(&path, transform, color, mode);
+        }
+        let transform = if rotation == 0 {
+            String::new()
+        } else {
+            format!(" transform=\"rotate({} {} {})\"", rotation, x, y)
+        };
+        self.push_pen_element(mode, svg_fill(color), svg_fill(ERASED), None, |fill| {
+            format!(
+                "<text x=\"{}\" y=\"{}\" font-family=\"{}\" font-size=\"{}\" {}{}>{}</text>",
+                x,
+                y,
+                svg_escape(&style.font),
+                style.height,
+                fill,
+                transform,
+                svg_escape(text)
+            )
+        });
+    }
+
+    /// Draw a straight line between two points.
+    pub fn draw_segment(
+        &mut self,
+        from: (f32, f32),
+        to: (f32, f32),
+        color: LogoColor,
+        style: &PenStyle,
+    ) {
+        let mut path = PathBuilder::new();
+        path.move_to(from.0, from.1);
+        path.line_to(to.0, to.1);
+        // a zero length line has nothing to rasterize, but is still kept in the svg
+        if let Some(path) = path.finish() {
+            self.stroke_path(&path, color, style);
+        }
+        let d = format!(
+            "M {} {} L {} {}",
+            svg_number(from.0),
+            svg_number(from.1),
+            svg_number(to.0),
+            svg_number(to.1)
+        );
+        let trace = Trace {
+            d: d.clone(),
+            length: (to.0 - from.0).hypot(to.1 - from.1),
+            color,
+            solid: style.pattern == PenPattern::Solid,
+        };
+        self.push_pen_element(
+            style.mode,
+            format!("{}{}", svg_stroke(color), style.svg_attributes()),
+            format!("{}{}", svg_stroke(ERASED), style.svg_attributes()),
+            Some(trace),
+            |stroke| format!("<path fill=\"none\" {} d=\"{}\"/>", stroke, d),
+        );
+    }
+
+    /// Outline the closed polygon through `points`.
+    pub fn draw_shape(&mut self, points: &[(f32, f32)], color: LogoColor, style: &PenStyle) {
+        let mut path = PathBuilder::new();
+        let mut d = Vec::new();
+        for (i, (x, y)) in points.iter().enumerate() {
+            if i == 0 {
+                path.move_to(*x, *y);
+                d.push(format!("M {} {}", svg_number(*x), svg_number(*y)));
+            } else {
+                path.line_to(*x, *y);
+                d.push(format!("L {} {}", svg_number(*x), svg_number(*y)));
+            }
+        }
+        path.close();
+        d.push("Z".to_string());
+        if let Some(path) = path.finish() {
+            self.stroke_path(&path, color, style);
+        }
+        let d = d.join(" ");
+        self.push_pen_element(
+            style.mode,
+            format!("{}{}", svg_stroke(color), style.svg_attributes()),
+            format!("{}{}", svg_stroke(ERASED), style.svg_attributes()),
+            None,
+            |stroke| format!("<path fill=\"none\" {} d=\"{}\"/>", stroke, d),
+        );
+    }
+
+    pub fn begin_fill(&mut self) -> Result<(), String> {
+        if self.fill_layer.is_some() {
+            return Err("fill already started".to_string());
+        }
+        self.fill_layer = Some(FillLayer {
+            element_index: self.elements.len(),
+            pixmap: Pixmap::new(self.width, self.height).ok_or("invalid canvas size")?,
+        });
+        Ok(())
+    }
+
+    /// Fill the polygon through `points` below everything drawn since `begin_fill`.
+    pub fn end_fill(&mut self, points: &[(i32, i32)], color: LogoColor) -> Result<(), String> {
+        let layer = self.fill_layer.take().ok_or("fill not started")?;
+        if points.len() >= 3 {
+            let mut path = PathBuilder::new();
+            let mut d = Vec::new();
+            for (i, (x, y)) in points.iter().enumerate() {
+                if i == 0 {
+                    path.move_to(*x as f32, *y as f32);
+                    d.push(format!("M {} {}", x, y));
+                } else {
+                    path.line_to(*x as f32, *y as f32);
+                    d.push(format!("L {} {}", x, y));
+                }
+            }
+            path.close();
+            d.push("Z".to_string());
+            if let Some(path) = path.finish() {
+                self.extend_bounds(path.bounds());
+                self.pixmap.fill_path(
+                    &path,
+                    &skia_paint(color),
+                    FillRule::Winding,
+                    self.transform,
+                    None,
+                );
+            }
+            self.erasing = false;
+            let timing = self.advance(None);
+            self.elements.insert(
+                layer.element_index,
+                animated(
+                    format!(
+                        "<path {} stroke=\"none\" d=\"{}\"/>",
+                        svg_fill(color),
+                        d.join(" ")
+                    ),
+                    timing,
+                ),
+            );
+        }
+        self.pixmap.draw_pixmap(
+            0,
+            0,
+            layer.pixmap.as_ref(),
+            &PixmapPaint::default(),
+            Transform::identity(),
+            None,
+        );
+        Ok(())
+    }
+
+    /// Flood fill the area of the same color around a point.
+    /// Only the png output is filled, svg has no way to describe it.
+    pub fn flood_fill(&mut self, x: i32, y: i32, color: LogoColor) {
+        let mut point = tiny_skia::Point::from_xy(x as f32, y as f32);
+        self.transform.map_point(&mut point);
+        let (x, y) = (point.x.round() as i32, point.y.round() as i32);
+        let (width, height) = (self.width as i32, self.height as i32);
+        if x < 0 || y < 0 || x >= width || y >= height {
+            return;
+        }
+        let pixels = self.pixmap.pixels();
+        let index = |x: i32, y: i32| (y * width + x) as usize;
+        let target = pixels[index(x, y)];
+        let Some(mut mask) = Mask::new(self.width, self.height) else {
+            return;
+        };
+        let area = mask.data_mut();
+        let mut pending = vec![(x, y)];
+        while let Some((x, y)) = pending.pop() {
+            if x < 0 || y < 0 || x >= width || y >= height {
+                continue;
+            }
+            let i = index(x, y);
+            if area[i] != 0 || pixels[i] != target {
+                continue;
+            }
+            area[i] = 255;
+            pending.extend([(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)]);
+        }
+        if let Some(rect) = Rect::from_xywh(0.0, 0.0, self.width as f32, self.height as f32) {
+            let mut paint = skia_paint(color);
+            paint.anti_alias = false;
+            self.pixmap
+                .fill_rect(rect, &paint, Transform::identity(), Some(&mask));
+        }
+    }
+
+    /// Save the `view` area of the drawing as an image of `size`.
+    pub fn save_svg<P: AsRef<Path>>(
+        &self,
+        path: P,
+        view: Rect,
+        size: (u32, u32),
+    ) -> Result<(), String> {
+        let (w, h) = size;
+        let (x, y, right, bottom) = (
+            svg_number(view.left()),
+            svg_number(view.top()),
+            svg_number(view.right()),
+            svg_number(view.bottom()),
+        );
+        let (view_width, view_height) = (svg_number(view.width()), svg_number(view.height()));
+        let mut svg = format!(
+            "<svg width=\"{w}\" height=\"{h}\" viewBox=\"{x} {y} {view_width} {view_height}\" xmlns=\"http://www.w3.org/2000/svg\">\n"
+        );
+        if self.erase_masks.is_empty() {
+            svg.push_str("    <defs/>\n");
+        } else {
+            svg.push_str("    <defs>\n");
+            for (id, mask) in self.erase_masks.iter().enumerate() {
+                svg.push_str(&format!(
+                    "        <mask id=\"erase{id}\" maskUnits=\"userSpaceOnUse\" x=\"{x}\" y=\"{y}\" width=\"{view_width}\" height=\"{view_height}\">\n"
+                ));
+                svg.push_str(&format!(
+                    "            <rect x=\"{x}\" y=\"{y}\" width=\"{view_width}\" height=\"{view_height}\" {}/>\n",
+                    svg_fill(LogoColor::rgb(255, 255, 255))
+                ));
+                for element in mask {
+                    svg.push_str("            ");
+                    svg.push_str(element);
+                    svg.push('\n');
+                }
+                svg.push_str("        </mask>\n");
+            }
+            svg.push_str("    </defs>\n");
+        }
+        if self.background.alpha > 0 {
+            svg.push_str(&format!(
+                "    <path {} stroke=\"none\" d=\"M {x} {y} L {right} {y} L {right} {bottom} L {x} {bottom} Z\"/>\n",
+                svg_fill(self.background)
+            ));
+        }
+        for element in self.elements.iter() {
+            svg.push_str("    ");
+            svg.push_str(element);
+            svg.push('\n');
+        }
+        for glyph in self.glyphs.iter().filter(|glyph| !glyph.is_empty()) {
+            // pointing right, the way motions rotate it, white like a new turtle's pen
+            svg.push_str(
+                "    <path fill=\"none\" stroke=\"#ffffff\" d=\"M 10 0 L -5 5 L -5 -5 Z\" visibility=\"hidden\">\n",
+            );
+            for motion in glyph.iter() {
+                svg.push_str("        ");
+                svg.push_str(motion);
+                svg.push('\n');
+            }
+            svg.push_str("    </path>\n");
+        }
+        svg.push_str("</svg>\n");
+        std::fs::write(path, svg).map_err(|e| e.to_string())
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let mut image = Pixmap::new(self.width, self.height).ok_or("invalid canvas size")?;
+        let background = self.background;
+        image.fill(tiny_skia::Color::from_rgba8(
+            background.red,
+            background.green,
+            background.blue,
+            background.alpha,
+        ));
+        image.draw_pixmap(
+            0,
+            0,
+            self.pixmap.as_ref(),
+            &PixmapPaint::default(),
+            Transform::identity(),
+            None,
+        );
+        image.save_png(path).map_err(|e| e.to_string())
+    }
+}
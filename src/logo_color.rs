@@ -0,0 +1,157 @@
+/// A pen color with transparency, the 16 indexed colors are a subset with full opacity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogoColor {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+// names accepted by SETPENCOLOR, the logo palette first, then common svg names
+const NAMED_COLORS: [(&str, (u8, u8, u8)); 24] = [
+    ("black", (0, 0, 0)),
+    ("blue", (0, 0, 255)),
+    ("cyan", (0, 255, 255)),
+    ("green", (0, 255, 0)),
+    ("red", (255, 0, 0)),
+    ("magenta", (255, 0, 255)),
+    ("yellow", (255, 255, 0)),
+    ("white", (255, 255, 255)),
+    ("brown", (165, 42, 42)),
+    ("tan", (210, 180, 140)),
+    ("forest", (34, 139, 34)),
+    ("aqua", (127, 255, 212)),
+    ("salmon", (250, 128, 114)),
+    ("purple", (128, 0, 128)),
+    ("orange", (255, 165, 0)),
+    ("grey", (128, 128, 128)),
+    ("gray", (128, 128, 128)),
+    ("pink", (255, 192, 203)),
+    ("navy", (0, 0, 128)),
+    ("teal", (0, 128, 128)),
+    ("olive", (128, 128, 0)),
+    ("maroon", (128, 0, 0)),
+    ("silver", (192, 192, 192)),
+    ("gold", (255, 215, 0)),
+];
+
+impl LogoColor {
+    pub fn rgb(red: u8, green: u8, blue: u8) -> Self {
+        LogoColor {
+            red,
+            green,
+            blue,
+            alpha: 255,
+        }
+    }
+
+    /// Build a color from `[r g b]` or `[r g b a]` components in the range 0-255.
+    pub fn from_components(components: &[i32]) -> Result<Self, String> {
+        if components.len() != 3 && components.len() != 4 {
+            return Err(format!(
+                "invalid color, expected [r g b] or [r g b a]: {:?}",
+                components
+            ));
+        }
+        let mut channels = [255u8; 4];
+        for (channel, component) in channels.iter_mut().zip(components) {
+            *channel = u8::try_from(*component)
+                .map_err(|_| format!("invalid color component: {}", component))?;
+        }
+        Ok(LogoColor {
+            red: channels[0],
+            green: channels[1],
+            blue: channels[2],
+            alpha: channels[3],
+        })
+    }
+
+    /// Parse a color word, either a name like `orange`, `transparent` or a hex value
+    /// like `#ff8800`, `#f80` or `#ff880080` with alpha.
+    pub fn from_word(word: &str) -> Result<Self, String> {
+        if word.eq_ignore_ascii_case("transparent") {
+            return Ok(LogoColor {
+                alpha: 0,
+                ..LogoColor::rgb(0, 0, 0)
+            });
+        }
+        if let Some(hex) = word.strip_prefix('#') {
+            if !hex.is_ascii() {
+                return Err(format!("invalid hex color: {}", word));
+            }
+            let digits = match hex.len() {
+                3 => hex.chars().flat_map(|c| [c, c]).collect::<String>(),
+                6 | 8 => hex.to_string(),
+                _ => return Err(format!("invalid hex color: {}", word)),
+            };
+            let channels = (0..digits.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&digits[i..i + 2], 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|_| format!("invalid hex color: {}", word))?;
+            return Ok(LogoColor {
+                red: channels[0],
+                green: channels[1],
+                blue: channels[2],
+                alpha: channels.get(3).copied().unwrap_or(255),
+            });
+        }
+        NAMED_COLORS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(word))
+            .map(|(_, (red, green, blue))| LogoColor::rgb(*red, *green, *blue))
+            .ok_or(format!("unknown color: {}", word))
+    }
+
+    pub fn to_hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+
+    /// The color as a logo list, alpha is only included when not opaque.
+    pub fn to_list(self) -> String {
+        if self.alpha == 255 {
+            format!("[{} {} {}]", self.red, self.green, self.blue)
+        } else {
+            format!("[{} {} {} {}]", self.red, self.green, self.blue, self.alpha)
+        }
+    }
+}
+
+impl From<unsvg::Color> for LogoColor {
+    fn from(color: unsvg::Color) -> Self {
+        LogoColor::rgb(color.red, color.green, color.blue)
+    }
+}
+
+/// Parse a palette file, one `index color` entry per line where the color is
+/// `r g b`, `[r g b a]`, a hex word or a name. `//` starts a comment.
+pub fn parse_palette(content: &str) -> Result<Vec<(usize, LogoColor)>, String> {
+    let mut entries = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.split("//").next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let invalid = || format!("invalid palette entry on line {}: {}", number + 1, line);
+        let mut words = line
+            .split(|c: char| c.is_whitespace() || c == '[' || c == ']')
+            .filter(|word| !word.is_empty());
+        let index = words
+            .next()
+            .and_then(|index| index.parse::<usize>().ok())
+            .ok_or_else(invalid)?;
+        let words = words.collect::<Vec<&str>>();
+        let color = match words.as_slice() {
+            [word] => LogoColor::from_word(word),
+            components => components
+                .iter()
+                .map(|component| component.parse::<i32>())
+                .collect::<Result<Vec<i32>, _>>()
+                .map_err(|_| invalid())
+                .and_then(|components| LogoColor::from_components(&components)),
+        }
+        .map_err(|e| format!("{} ({})", invalid(), e))?;
+        entries.push((index, color));
+    }
+    Ok(entries)
+}
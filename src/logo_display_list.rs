@@ -0,0 +1,134 @@
+use std::ops::Range;
+
+use crate::logo_canvas::{LabelStyle, LogoCanvas, PenMode, PenStyle, TurtleArc};
+use crate::logo_color::LogoColor;
+
+/// One drawing operation of a program, in canvas pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawOp {
+    Segment {
+        from: (f32, f32),
+        to: (f32, f32),
+        color: LogoColor,
+        style: PenStyle,
+    },
+    Arc {
+        x: i32,
+        y: i32,
+        arc: TurtleArc,
+        color: LogoColor,
+        style: PenStyle,
+    },
+    Label {
+        x: i32,
+        y: i32,
+        direction: i32,
+        text: String,
+        color: LogoColor,
+        mode: PenMode,
+        style: LabelStyle,
+    },
+    /// a closed outline, the turtle shape of STAMP and SHOWTURTLE
+    Shape {
+        points: Vec<(f32, f32)>,
+        color: LogoColor,
+        style: PenStyle,
+    },
+    BeginFill,
+    EndFill {
+        points: Vec<(i32, i32)>,
+        color: LogoColor,
+    },
+    FloodFill {
+        x: i32,
+        y: i32,
+        color: LogoColor,
+    },
+    Clear,
+    Background(LogoColor),
+    /// the turtle moved without drawing or turned, shown or hidden, and is now at `to`
+    Motion {
+        from: (f32, f32),
+        to: (f32, f32),
+        heading: i32,
+        visible: bool,
+    },
+}
+
+/// An operation with the turtle that did it and where in the program it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisplayItem {
+    pub op: DrawOp,
+    pub turtle: usize,
+    /// bytes of the statement in the program, inside blocks and procedure bodies too
+    pub span: Option<Range<usize>>,
+}
+
+/// Everything drawn, in order, so it can be drawn again on another backend.
+#[derive(Debug, Clone, Default)]
+pub struct DisplayList {
+    items: Vec<DisplayItem>,
+}
+
+impl DisplayList {
+    pub fn push(&mut self, item: DisplayItem) {
+        self.items.push(item);
+    }
+
+    pub fn items(&self) -> &[DisplayItem] {
+        &self.items
+    }
+
+    pub fn replay(&self, backend: &mut impl DisplayBackend) -> Result<(), String> {
+        self.items().iter().try_for_each(|item| backend.apply(item))
+    }
+}
+
+/// Something that can draw the operations of a display list.
+pub trait DisplayBackend {
+    fn apply(&mut self, item: &DisplayItem) -> Result<(), String>;
+}
+
+impl DisplayBackend for LogoCanvas {
+    fn apply(&mut self, item: &DisplayItem) -> Result<(), String> {
+        self.set_turtle(item.turtle);
+        match &item.op {
+            DrawOp::Segment {
+                from,
+                to,
+                color,
+                style,
+            } => self.draw_segment(*from, *to, *color, style),
+            DrawOp::Arc {
+                x,
+                y,
+                arc,
+                color,
+                style,
+            } => self.draw_arc(*x, *y, arc, *color, style),
+            DrawOp::Label {
+                x,
+                y,
+                direction,
+                text,
+                color,
+                mode,
+                style,
+            } => self.draw_label(*x, *y, *direction, text, *color, *mode, style),
+            DrawOp::Shape {
+                points,
+                color,
+                style,
+            } => self.draw_shape(points, *color, style),
+            DrawOp::BeginFill => return self.begin_fill(),
+            DrawOp::EndFill { points, color } => return self.end_fill(points, *color),
+            DrawOp::FloodFill { x, y, color } => self.flood_fill(*x, *y, *color),
+            DrawOp::Clear => self.clear(),
+            DrawOp::Background(color) => self.set_background(*color),
+            DrawOp::Motion {
+                from, to, heading, ..
+            } => self.move_turtle(*from, *to, *heading),
+        }
+        Ok(())
+    }
+}
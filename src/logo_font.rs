@@ -0,0 +1,67 @@
+use tiny_skia::{Path, PathBuilder};
+use ttf_parser::{Face, OutlineBuilder};
+
+// png labels always use this font, svg labels leave the font to the viewer
+const FONT: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+
+/// Collects glyph outlines into a path, scaled to pixels with the y axis pointing down.
+struct GlyphOutline {
+    path: PathBuilder,
+    scale: f32,
+    offset: f32,
+}
+
+impl GlyphOutline {
+    fn point(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.offset + x * self.scale, -y * self.scale)
+    }
+}
+
+impl OutlineBuilder for GlyphOutline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.point(x, y);
+        self.path.move_to(x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.point(x, y);
+        self.path.line_to(x, y);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (x1, y1) = self.point(x1, y1);
+        let (x, y) = self.point(x, y);
+        self.path.quad_to(x1, y1, x, y);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (x1, y1) = self.point(x1, y1);
+        let (x2, y2) = self.point(x2, y2);
+        let (x, y) = self.point(x, y);
+        self.path.cubic_to(x1, y1, x2, y2, x, y);
+    }
+
+    fn close(&mut self) {
+        self.path.close();
+    }
+}
+
+/// Outline of a line of text with its baseline starting at the origin,
+/// `None` when there is nothing to draw.
+pub fn text_path(text: &str, size: f32) -> Option<Path> {
+    let face = Face::parse(FONT, 0).ok()?;
+    let mut outline = GlyphOutline {
+        path: PathBuilder::new(),
+        scale: size / face.units_per_em() as f32,
+        offset: 0.0,
+    };
+    for ch in text.chars() {
+        let Some(glyph) = face.glyph_index(ch).or_else(|| face.glyph_index('?')) else {
+            continue;
+        };
+        face.outline_glyph(glyph, &mut outline);
+        let advance = face.glyph_hor_advance(glyph).unwrap_or_default();
+        outline.offset += advance as f32 * outline.scale;
+    }
+    outline.path.finish()
+}
@@ -1,4 +1,10 @@
-use crate::{logo_runner::LogoRunner, r#const};
+use crate::{
+    logo_canvas,
+    logo_canvas::PenMode,
+    logo_color::LogoColor,
+    logo_runner::{Boundary, LogoRunner},
+    r#const,
+};
 use r#const::*;
 use std::{
     collections::HashMap,
@@ -11,13 +17,32 @@ pub struct LogoInterpreter {
     // ignore the contention in single thread
     var_table: Arc<Mutex<HashMap<String, String>>>,
     procedure_table: Arc<Mutex<HashMap<String, LogoProcedure>>>,
-    arg_vars_table: HashMap<String, String>,
+    // shared with the conditional bodies evaluated inside the same procedure call
+    arg_vars_table: Arc<Mutex<HashMap<String, String>>>,
+    options: LogoOptions,
+    // where the source code starts in the program, blocks and procedure bodies run
+    // from a part of it and the display list points back into the program
+    source_offset: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogoOptions {
+    /// report redefined procedures and keyword-like names as warnings instead of errors
+    pub warn_redefinitions: bool,
+    /// match primitives, procedures and variables exactly instead of ignoring case
+    pub case_sensitive: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct LogoProcedure {
     source_code: String,
+    // where the body starts in the program
+    source_offset: usize,
     args: Vec<String>,
+    // name and default expression, evaluated when the argument is omitted
+    optional_args: Vec<(String, String)>,
+    // collects every argument after the optional ones as a list
+    rest_arg: Option<String>,
 }
 
 impl LogoInterpreter {
@@ -26,15 +51,27 @@ impl LogoInterpreter {
             source_code,
             Arc::new(Mutex::new(HashMap::new())),
             Arc::new(Mutex::new(HashMap::new())),
-            HashMap::new(),
+            Arc::new(Mutex::new(HashMap::new())),
+            LogoOptions::default(),
         )
     }
 
+    pub fn with_options(mut self, options: LogoOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn with_source_offset(mut self, offset: usize) -> Self {
+        self.source_offset = offset;
+        self
+    }
+
     pub fn new(
         source_code: String,
         var_table: Arc<Mutex<HashMap<String, String>>>,
         procedure_table: Arc<Mutex<HashMap<String, LogoProcedure>>>,
-        arg_vars_table: HashMap<String, String>,
+        arg_vars_table: Arc<Mutex<HashMap<String, String>>>,
+        options: LogoOptions,
     ) -> Self {
         Self {
             source_code,
@@ -42,6 +79,8 @@ impl LogoInterpreter {
             var_table,
             arg_vars_table,
             procedure_table,
+            options,
+            source_offset: 0,
         }
     }
 
@@ -55,6 +94,7 @@ impl LogoInterpreter {
     }
 
     fn interpret_expr(&mut self, runner: &mut LogoRunner) -> Result<(), String> {
+        let start = self.cursor;
         let token = self.next_token();
         self.cursor += token.len();
         let token: &str = token.trim();
@@ -62,18 +102,36 @@ impl LogoInterpreter {
             self.cursor += 1;
             return Ok(());
         }
-        let expr = self.collect_expr(Self::get_terminator(&token))?;
-        self.cursor += expr.len();
-        match token {
-            COMMENT => {
+        let keyword = self.fold(token);
+        let terminator = Self::get_terminator(&keyword);
+        let expr_start = self.cursor;
+        let mut expr = self.collect_expr(terminator)?;
+        if terminator == "\n" {
+            // only take the arguments of this command, the rest of the line is the next statement
+            let (consumed, statement) = self.split_statement(&keyword, &expr);
+            self.cursor += consumed;
+            expr = statement;
+        } else {
+            self.cursor += expr.len();
+        }
+        if let Some(statement) = self.source_code.get(start..self.cursor) {
+            let start = self.source_offset + start;
+            runner.set_source_span(Some(start..start + statement.trim_end().len()));
+        }
+        match keyword.as_str() {
+            t if t.starts_with(COMMENT) => {
                 // skip comments
                 Ok(())
             }
+            t if Self::is_expr_start(t) => Err(format!("unexpected argument: {}", token)),
             t if Self::is_builtin_fn(t) => self.evaluate_builtin_fn(t, expr, runner),
             MAKE => self.evaluate_make_statement(expr, runner),
-            ADDASSIGN => self.evaluate_add_assign_statement(expr, runner),
-            IF | WHILE => self.evaluate_conditional_statement(token, expr, runner),
-            TO => self.evaluate_procedure_definition(expr, runner),
+            t @ (ADDASSIGN | SUBASSIGN | MULASSIGN | DIVASSIGN) => {
+                self.evaluate_compound_assign_statement(t, expr, runner)
+            }
+            t @ (IF | WHILE) => self.evaluate_conditional_statement(t, expr, expr_start, runner),
+            ASK => self.evaluate_ask_statement(expr, expr_start, runner),
+            TO => self.evaluate_procedure_definition(expr, expr_start, runner),
             _ => self.find_evaluate_procedure(token, expr, runner),
         }
     }
@@ -86,24 +144,51 @@ impl LogoInterpreter {
     ) -> Result<(), String> {
         let val = self.evaluate_expr(&expr, runner)?;
         match token {
-            PENUP | PENDOWN => {
-                if val.len() != 0 {
+            PENUP | PENDOWN | PENPAINT | PENERASE | PENREVERSE | BEGINFILL | ENDFILL | FILL
+            | HOME | CLEARSCREEN | CLEAN | WRAP | WINDOW | FENCE | NEWTURTLE | PUSHTURTLE
+            | POPTURTLE | SHOWTURTLE | HIDETURTLE | STAMP | MIRROR | PUSHTRANSFORM
+            | POPTRANSFORM => {
+                if !val.is_empty() {
                     return Err(format!("invalid argument: {}", expr));
                 }
-                if token == "PENUP" {
-                    runner.pen_up();
-                } else {
-                    runner.pen_down();
+                match token {
+                    PENUP => runner.pen_up(),
+                    PENDOWN => runner.pen_down(),
+                    PENPAINT => runner.set_pen_mode(PenMode::Paint),
+                    PENERASE => runner.set_pen_mode(PenMode::Erase),
+                    PENREVERSE => runner.set_pen_mode(PenMode::Reverse),
+                    BEGINFILL => runner.begin_fill()?,
+                    ENDFILL => runner.end_fill()?,
+                    FILL => runner.fill()?,
+                    HOME => runner.home(),
+                    CLEARSCREEN => runner.clear_screen()?,
+                    CLEAN => runner.clean()?,
+                    WRAP => runner.set_boundary(Boundary::Wrap),
+                    WINDOW => runner.set_boundary(Boundary::Window),
+                    FENCE => runner.set_boundary(Boundary::Fence),
+                    NEWTURTLE => {
+                        runner.new_turtle();
+                    }
+                    PUSHTURTLE => runner.push_turtle(),
+                    POPTURTLE => runner.pop_turtle()?,
+                    SHOWTURTLE => runner.show_turtle(),
+                    HIDETURTLE => runner.hide_turtle(),
+                    STAMP => runner.stamp()?,
+                    MIRROR => runner.mirror(),
+                    PUSHTRANSFORM => runner.push_transform(),
+                    POPTRANSFORM => runner.pop_transform()?,
+                    _ => unreachable!(),
                 }
             }
-            FORWARD | BACK | LEFT | RIGHT | SETX | SETY | SETPENCOLOR | TURN | SETHEADING => {
+            FORWARD | BACK | LEFT | RIGHT | SETX | SETY | SETPENSIZE | TURN | SETHEADING
+            | CIRCLE | SETLABELHEIGHT | TELL | ROTATE => {
+                if val.is_empty() {
+                    return Err(format!("not enough arguments for {}", token));
+                }
                 if val.len() != 1 {
                     return Err(format!("invalid argument: {}", expr));
                 }
-                let val: i32 = val
-                    .iter()
-                    .next()
-                    .unwrap()
+                let val: i32 = val[0]
                     .parse()
                     .map_err(|_| format!("invalid argument: {}", expr))?;
                 match token {
@@ -111,29 +196,187 @@ impl LogoInterpreter {
                     BACK => runner.draw_backward(val)?,
                     LEFT => runner.draw_left(val)?,
                     RIGHT => runner.draw_right(val)?,
-                    SETX => runner.set_pos(val, runner.get_pos_y()),
-                    SETY => runner.set_pos(runner.get_pos_x(), val),
-                    SETPENCOLOR => {
-                        if val > 15 || val < 0 {
-                            return Err("invalid color".to_string());
+                    SETX => runner.set_pos(val, runner.get_pos_y())?,
+                    SETY => runner.set_pos(runner.get_pos_x(), val)?,
+                    SETPENSIZE => {
+                        if val <= 0 {
+                            return Err(format!("invalid pen size: {}", val));
                         }
-                        runner.set_color(val as usize);
+                        runner.set_pen_size(val as u32);
                     }
                     TURN | SETHEADING => runner.turn_degree(val),
+                    CIRCLE => runner.draw_arc(360, val)?,
+                    SETLABELHEIGHT => {
+                        if val <= 0 {
+                            return Err(format!("invalid label height: {}", val));
+                        }
+                        runner.set_label_height(val as u32);
+                    }
+                    ROTATE => runner.rotate(val),
+                    TELL => {
+                        let turtle =
+                            usize::try_from(val).map_err(|_| format!("no such turtle: {}", val))?;
+                        runner.tell(turtle)?;
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            SETPENCOLOR | SETFILLCOLOR | SETBACKGROUND | SETPENPATTERN | SETLINECAP
+            | SETLINEJOIN | LABEL | SETLABELFONT | SETSHAPE | SETSCALE => {
+                if val.is_empty() {
+                    return Err(format!("not enough arguments for {}", token));
+                }
+                if val.len() != 1 {
+                    return Err(format!("invalid argument: {}", expr));
+                }
+                match token {
+                    SETPENCOLOR => match val[0].parse::<i32>() {
+                        Ok(index) => {
+                            let index = usize::try_from(index).map_err(|_| "invalid color")?;
+                            runner.set_color(index)?
+                        }
+                        Err(_) => runner.set_color_rgb(Self::parse_color(&val[0])?),
+                    },
+                    SETFILLCOLOR | SETBACKGROUND => {
+                        let color = match val[0].parse::<usize>() {
+                            Ok(index) => runner.get_palette(index).ok_or("invalid color")?,
+                            Err(_) => Self::parse_color(&val[0])?,
+                        };
+                        if token == SETFILLCOLOR {
+                            runner.set_fill_color(color);
+                        } else {
+                            runner.set_background(color)?;
+                        }
+                    }
+                    SETPENPATTERN => {
+                        runner.set_pen_pattern(logo_canvas::PenPattern::parse(&val[0])?)
+                    }
+                    SETLINECAP => runner.set_line_cap(logo_canvas::parse_line_cap(&val[0])?),
+                    SETLINEJOIN => runner.set_line_join(logo_canvas::parse_line_join(&val[0])?),
+                    SETSHAPE => runner.set_shape(Self::parse_shape(&val[0])?)?,
+                    SETSCALE => runner.set_scale(
+                        val[0]
+                            .parse()
+                            .map_err(|_| format!("invalid argument: {}", expr))?,
+                    )?,
+                    // a list is written as its words separated by spaces
+                    LABEL | SETLABELFONT => {
+                        let text = list_items(&val[0])
+                            .map(|items| items.join(" "))
+                            .unwrap_or(val[0].clone());
+                        if token == LABEL {
+                            runner.label(&text)?;
+                        } else {
+                            runner.set_label_font(text);
+                        }
+                    }
                     _ => unreachable!(),
                 }
             }
+            SETPOS => {
+                if val.is_empty() {
+                    return Err(format!("not enough arguments for {}", token));
+                }
+                let (x, y) = Self::parse_pos(&val[0])
+                    .filter(|_| val.len() == 1)
+                    .ok_or(format!("invalid argument, expected [x y]: {}", expr))?;
+                runner.set_pos(x, y)?;
+            }
+            ARC | SETXY | TRANSLATE => {
+                if val.len() < 2 {
+                    return Err(format!("not enough arguments for {}", token));
+                }
+                let val = val
+                    .iter()
+                    .map(|v| v.parse::<i32>())
+                    .collect::<Result<Vec<i32>, _>>()
+                    .ok()
+                    .filter(|val| val.len() == 2)
+                    .ok_or(format!("invalid argument: {}", expr))?;
+                match token {
+                    ARC => runner.draw_arc(val[0], val[1])?,
+                    SETXY => runner.set_pos(val[0], val[1])?,
+                    TRANSLATE => runner.translate(val[0], val[1]),
+                    _ => unreachable!(),
+                }
+            }
+            SETPALETTE => {
+                if val.len() < 2 {
+                    return Err(format!("not enough arguments for {}", token));
+                }
+                if val.len() != 2 {
+                    return Err(format!("invalid argument: {}", expr));
+                }
+                let index: usize = val[0]
+                    .parse()
+                    .map_err(|_| format!("invalid palette index: {}", val[0]))?;
+                runner.set_palette(index, Self::parse_color(&val[1])?)?;
+            }
             _ => return Err(format!("unimplemented builtin function: {}", token)),
         }
         Ok(())
     }
 
+    /// Parse a color given as a `[r g b]` list, a hex word or a color name.
+    fn parse_color(val: &str) -> Result<LogoColor, String> {
+        match list_items(val) {
+            Some(items) => {
+                let components = items
+                    .iter()
+                    .map(|item| item.parse::<i32>())
+                    .collect::<Result<Vec<i32>, _>>()
+                    .map_err(|_| format!("invalid color: {}", val))?;
+                LogoColor::from_components(&components)
+            }
+            None => LogoColor::from_word(val),
+        }
+    }
+
+    fn parse_pos(val: &str) -> Option<(i32, i32)> {
+        match list_items(val)?.as_slice() {
+            [x, y] => x.parse().ok().zip(y.parse().ok()),
+            _ => None,
+        }
+    }
+
+    /// A shape is a list of `[x y]` points, x to the right of the turtle and y ahead of it.
+    fn parse_shape(val: &str) -> Result<Vec<(f32, f32)>, String> {
+        let points = list_items(val).ok_or(format!("invalid shape: {}", val))?;
+        points
+            .iter()
+            .map(|point| {
+                match list_items(point).as_deref() {
+                    Some([x, y]) => x.parse().ok().zip(y.parse().ok()),
+                    _ => None,
+                }
+                .ok_or(format!("invalid shape point: {}", point))
+            })
+            .collect()
+    }
+
     fn evaluate_expr(&self, expr: &str, runner: &LogoRunner) -> Result<Vec<String>, String> {
-        let mut items = expr.trim().split_whitespace().collect::<Vec<&str>>();
+        let mut items = Self::tokenize(expr)
+            .into_iter()
+            .map(|(_, item)| item)
+            .collect::<Vec<&str>>();
         let mut stack: Vec<String> = Vec::new();
+        // stack heights where the currently open lists begin
+        let mut lists: Vec<usize> = Vec::new();
         items.reverse();
         for item in items {
-            match item {
+            match self.fold(item).as_str() {
+                LIST_END => lists.push(stack.len()),
+                LIST_START => {
+                    let start = lists
+                        .pop()
+                        .ok_or(format!("invalid expression, unmatched [ : {}", expr))?;
+                    let mut list = stack.split_off(start);
+                    list.reverse();
+                    stack.push(format!("[{}]", list.join(" ")));
+                }
+                // words in a list are taken as they are, like UCBLogo: [:n "4] is the
+                // words :n and "4, nothing in a list is looked up or run
+                _ if !lists.is_empty() => stack.push(item.to_string()),
                 PLUS | MINUS | TIMES | DIVIDE => {
                     let left = stack
                         .pop()
@@ -150,7 +393,7 @@ impl LogoInterpreter {
                             format!("invalid expression, right operand is not a number: {}", e)
                         })?;
 
-                    let result = match item {
+                    let result = match self.fold(item).as_str() {
                         PLUS => left + right,
                         MINUS => left - right,
                         TIMES => left * right,
@@ -159,17 +402,17 @@ impl LogoInterpreter {
                     };
                     stack.push(result.to_string());
                 }
-                EQ | NE | LT | GT | AND | OR => {
+                op @ (EQ | NE | LT | GT | AND | OR) => {
                     let left = stack
                         .pop()
                         .ok_or(format!("invalid expression, stack underflow : {}", expr))?;
                     let right = stack
                         .pop()
                         .ok_or(format!("invalid expression, stack underflow : {}", expr))?;
-                    let result = self.logical_op(&left, &right, item)?;
+                    let result = self.logical_op(&left, &right, op)?;
                     stack.push(result.to_string().to_uppercase());
                 }
-                item if item.starts_with("\"") => {
+                _ if item.starts_with('"') => {
                     // literal
                     if let Some(literal) = item.strip_prefix("\"") {
                         stack.push(literal.to_string());
@@ -177,16 +420,18 @@ impl LogoInterpreter {
                     }
                     return Err(format!("invalid literal: {}", item));
                 }
-                item if item.starts_with(":") => {
+                _ if item.starts_with(':') => {
                     // variable
-                    if let Some(var_name) = item.strip_prefix(":") {
-                        let vt = self.var_table.lock().map_err(|e| e.to_string())?;
-                        if let Some(var_value) = vt.get(var_name) {
+                    if let Some(var_name) = item.strip_prefix(':') {
+                        let var_name = self.fold(var_name);
+                        // procedure inputs hide variables of the same name
+                        let at = self.arg_vars_table.lock().map_err(|e| e.to_string())?;
+                        if let Some(var_value) = at.get(&var_name) {
                             stack.push(var_value.to_string());
                             continue;
                         }
-                        // find in args table
-                        if let Some(var_value) = self.arg_vars_table.get(var_name) {
+                        let vt = self.var_table.lock().map_err(|e| e.to_string())?;
+                        if let Some(var_value) = vt.get(&var_name) {
                             stack.push(var_value.to_string());
                             continue;
                         }
@@ -196,15 +441,60 @@ impl LogoInterpreter {
                 XCOR => stack.push(runner.get_pos_x().to_string()),
                 YCOR => stack.push(runner.get_pos_y().to_string()),
                 HEADING => stack.push(runner.get_direction().to_string()),
-                COLOR => stack.push(runner.get_color_index().to_string()),
+                POS => stack.push(format!("[{} {}]", runner.get_pos_x(), runner.get_pos_y())),
+                COLOR => stack.push(match runner.get_color_index() {
+                    Some(index) => index.to_string(),
+                    None => runner.get_color().to_list(),
+                }),
+                PENCOLOR => stack.push(runner.get_color().to_list()),
+                BACKGROUND => stack.push(runner.get_background().to_list()),
+                WHO => stack.push(runner.who().to_string()),
+                PENDOWNP => stack.push(runner.is_pen_down().to_string().to_uppercase()),
+                PENSIZE => stack.push(runner.get_pen_size().to_string()),
+                SHOWNP => stack.push(runner.is_turtle_shown().to_string().to_uppercase()),
+                CANVASWIDTH => stack.push(runner.get_width().to_string()),
+                CANVASHEIGHT => stack.push(runner.get_height().to_string()),
+                BOUNDS => {
+                    let [xmin, ymin, xmax, ymax] = runner.bounds();
+                    stack.push(format!("[{} {} {} {}]", xmin, ymin, xmax, ymax));
+                }
+                t @ (TOWARDS | DISTANCE) => {
+                    let pos = stack
+                        .pop()
+                        .ok_or(format!("not enough arguments for {}", t))?;
+                    let (x, y) = Self::parse_pos(&pos)
+                        .ok_or(format!("invalid argument, expected [x y]: {}", pos))?;
+                    let result = if t == TOWARDS {
+                        runner.towards(x, y)
+                    } else {
+                        runner.distance(x, y)
+                    };
+                    stack.push(result.to_string());
+                }
+                PALETTE => {
+                    let index = stack
+                        .pop()
+                        .ok_or(format!("not enough arguments for {}", PALETTE))?;
+                    let color = index
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|index| runner.get_palette(index))
+                        .ok_or(format!("invalid palette index: {}", index))?;
+                    stack.push(color.to_list());
+                }
                 _ => {}
             }
         }
+        if !lists.is_empty() {
+            return Err(format!("invalid expression, unmatched ] : {}", expr));
+        }
         stack.reverse();
         Ok(stack.to_vec())
     }
 
     fn logical_op(&self, left: &str, right: &str, op: &str) -> Result<bool, String> {
+        let left = self.fold_boolean(left);
+        let right = self.fold_boolean(right);
         // boolean comparison
         if left == TRUE || left == FALSE || right == TRUE || right == FALSE {
             if left != TRUE && left != FALSE {
@@ -229,6 +519,14 @@ impl LogoInterpreter {
                 _ => return Err(format!("invalid operator: {}", op)),
             }
         }
+        // lists are only compared for equality
+        if list_items(left).is_some() || list_items(right).is_some() {
+            match op {
+                EQ => return Ok(left == right),
+                NE => return Ok(left != right),
+                _ => return Err(format!("invalid operator for lists: {}", op)),
+            }
+        }
         // numeric comparison
         let left = left
             .parse::<i32>()
@@ -237,37 +535,65 @@ impl LogoInterpreter {
             .parse::<i32>()
             .map_err(|_| format!("invalid expression: {}", right))?;
         match op {
-            GT => return Ok(left > right),
-            LT => return Ok(left < right),
-            EQ => return Ok(left == right),
-            NE => return Ok(left != right),
-            _ => return Err(format!("invalid operator: {}", op)),
+            GT => Ok(left > right),
+            LT => Ok(left < right),
+            EQ => Ok(left == right),
+            NE => Ok(left != right),
+            _ => Err(format!("invalid operator: {}", op)),
         }
     }
 
     fn collect_expr(&self, terminator: &str) -> Result<String, String> {
         let mut expr = Vec::new();
-        let mut cursor = self.cursor;
         let mut skip = 0;
-        while let Some(ch) = self.source_code.chars().nth(cursor) {
+        let mut block_start = self.cursor;
+        let mut in_comment = false;
+        // byte offsets, so slices of the source stay on character boundaries
+        for (offset, ch) in self.source_code[self.cursor..].char_indices() {
+            let cursor = self.cursor + offset;
+            let next = cursor + ch.len_utf8();
             expr.push(ch);
-            let start_pos: i32 = cursor as i32 - terminator.len() as i32 + 1;
-            if start_pos >= 0 {
-                let matcher = &self.source_code[start_pos as usize..cursor + 1];
-                // dirty fix for nested statement
-                if terminator == "]" {
-                    if ch == '[' {
-                        skip += 1;
+            // brackets and terminators inside a trailing comment don't count
+            if ch == '\n' {
+                in_comment = false;
+            } else if !in_comment && Self::comment_at(&self.source_code, cursor) {
+                in_comment = true;
+            }
+            if in_comment {
+                continue;
+            }
+            if terminator == LIST_END {
+                // lists may appear in the condition, the block is the one following it
+                if ch == '[' {
+                    if skip == 0 {
+                        block_start = cursor;
                     }
-                }
-                if matcher == "]" {
+                    skip += 1;
+                } else if ch == ']' {
                     skip -= 1;
+                    if skip < 0
+                        || (skip == 0
+                            && self.is_complete_expr(&self.source_code[self.cursor..block_start]))
+                    {
+                        return Ok(expr.iter().collect::<String>());
+                    }
+                }
+            } else if terminator == END {
+                if self.ends_procedure(next) {
+                    return Ok(expr.iter().collect::<String>());
                 }
-                if matcher == terminator && skip <= 0 {
+            } else {
+                let matcher = next
+                    .checked_sub(terminator.len())
+                    .and_then(|start| self.source_code.get(start..next));
+                if matcher.is_some_and(|matcher| self.fold(matcher) == terminator) {
                     return Ok(expr.iter().collect::<String>());
                 }
             }
-            cursor += 1;
+        }
+        if terminator == "\n" {
+            // the last line of a file or a one-line block
+            return Ok(expr.iter().collect::<String>());
         }
         Err(format!(
             "unterminated statement: {}",
@@ -275,11 +601,39 @@ impl LogoInterpreter {
         ))
     }
 
+    /// Whether the source up to the byte offset `end` finishes an `END` starting its line,
+    /// so names like `legend` or `send` don't close the procedure.
+    fn ends_procedure(&self, end: usize) -> bool {
+        let (Some(before), Some(after)) = (self.source_code.get(..end), self.source_code.get(end..))
+        else {
+            return false;
+        };
+        let line_start = before.rfind('\n').map_or(0, |pos| pos + 1);
+        let word = before[line_start..].trim_start();
+        let word_end = after.chars().next().is_none_or(char::is_whitespace);
+        word_end && self.fold(word) == END
+    }
+
+    /// Normalize a keyword, procedure or variable name for lookup.
+    fn fold(&self, name: &str) -> String {
+        if self.options.case_sensitive {
+            name.to_string()
+        } else {
+            name.to_uppercase()
+        }
+    }
+
+    fn fold_boolean<'a>(&self, val: &'a str) -> &'a str {
+        match self.fold(val).as_str() {
+            TRUE => TRUE,
+            FALSE => FALSE,
+            _ => val,
+        }
+    }
+
     fn next_token(&self) -> String {
         let mut token = Vec::new();
-        let mut cursor = self.cursor;
-        while let Some(ch) = self.source_code.chars().nth(cursor) {
-            cursor += 1;
+        for ch in self.source_code[self.cursor..].chars() {
             if ch == ' ' || ch == '\n' {
                 break;
             }
@@ -289,19 +643,223 @@ impl LogoInterpreter {
     }
 
     fn is_builtin_fn(token: &str) -> bool {
-        match token {
-            PENUP | PENDOWN | FORWARD | BACK | LEFT | RIGHT | SETX | SETY | SETPENCOLOR | TURN
-            | SETHEADING => true,
-            _ => false,
+        matches!(
+            token,
+            PENUP
+                | PENDOWN
+                | PENPAINT
+                | PENERASE
+                | PENREVERSE
+                | FORWARD
+                | BACK
+                | LEFT
+                | RIGHT
+                | SETX
+                | SETY
+                | SETPENCOLOR
+                | SETPALETTE
+                | SETBACKGROUND
+                | SETXY
+                | SETPOS
+                | HOME
+                | CLEARSCREEN
+                | CLEAN
+                | WRAP
+                | WINDOW
+                | FENCE
+                | LABEL
+                | SETLABELHEIGHT
+                | SETLABELFONT
+                | ARC
+                | CIRCLE
+                | SETFILLCOLOR
+                | BEGINFILL
+                | ENDFILL
+                | FILL
+                | NEWTURTLE
+                | TELL
+                | PUSHTURTLE
+                | POPTURTLE
+                | SHOWTURTLE
+                | HIDETURTLE
+                | STAMP
+                | SETSHAPE
+                | SETSCALE
+                | TRANSLATE
+                | ROTATE
+                | MIRROR
+                | PUSHTRANSFORM
+                | POPTRANSFORM
+                | SETPENSIZE
+                | SETPENPATTERN
+                | SETLINECAP
+                | SETLINEJOIN
+                | TURN
+                | SETHEADING
+        )
+    }
+
+    fn comment_at(line: &str, pos: usize) -> bool {
+        line.get(pos..).is_some_and(|rest| rest.starts_with(COMMENT))
+            && line[..pos].chars().last().is_none_or(char::is_whitespace)
+    }
+
+    fn strip_comment(line: &str) -> &str {
+        let mut positions = line.char_indices().map(|(pos, _)| pos);
+        match positions.find(|&pos| Self::comment_at(line, pos)) {
+            Some(pos) => &line[..pos],
+            None => line,
         }
     }
 
+    /// Split a line into the arguments of the command and whatever follows them.
+    /// Returns the length consumed from the line and the arguments without comments.
+    fn split_statement(&self, keyword: &str, line: &str) -> (usize, String) {
+        let code = Self::strip_comment(line);
+        let tokens = Self::tokenize(code);
+        let max_args = match self.arity(keyword) {
+            Some(max_args) => max_args,
+            None => return (line.len(), code.to_string()),
+        };
+        let mut end = 0;
+        let mut count = 0;
+        while max_args.is_none_or(|max| count < max) {
+            match self.expr_span(&tokens[end..]) {
+                Some(span) => end += span,
+                None => break,
+            }
+            count += 1;
+        }
+        match tokens.get(end) {
+            Some((pos, _)) => (*pos, code[..*pos].to_string()),
+            None => (line.len(), code.to_string()),
+        }
+    }
+
+    /// Maximum number of arguments a command takes, `Some(None)` when unbounded
+    /// and `None` if it should take the whole line.
+    fn arity(&self, keyword: &str) -> Option<Option<usize>> {
+        match keyword {
+            PENUP | PENDOWN | PENPAINT | PENERASE | PENREVERSE | BEGINFILL | ENDFILL | FILL
+            | HOME | CLEARSCREEN | CLEAN | WRAP | WINDOW | FENCE | NEWTURTLE | PUSHTURTLE
+            | POPTURTLE | SHOWTURTLE | HIDETURTLE | STAMP | MIRROR | PUSHTRANSFORM
+            | POPTRANSFORM => Some(Some(0)),
+            FORWARD | BACK | LEFT | RIGHT | SETX | SETY | SETPENCOLOR | SETPENSIZE | TURN
+            | SETHEADING | SETPENPATTERN | SETLINECAP | SETLINEJOIN | SETFILLCOLOR
+            | SETBACKGROUND | CIRCLE | LABEL | SETLABELHEIGHT | SETLABELFONT | SETPOS | TELL
+            | SETSHAPE | SETSCALE | ROTATE => Some(Some(1)),
+            MAKE | ADDASSIGN | SUBASSIGN | MULASSIGN | DIVASSIGN | SETPALETTE | ARC | SETXY
+            | TRANSLATE => Some(Some(2)),
+            _ => {
+                let pt = self.procedure_table.lock().ok()?;
+                let procedure = pt.get(keyword)?;
+                match procedure.rest_arg {
+                    Some(_) => Some(None),
+                    None => Some(Some(procedure.args.len() + procedure.optional_args.len())),
+                }
+            }
+        }
+    }
+
+    /// Number of tokens making up the expression at the start of `tokens`,
+    /// `None` if they don't start with a complete expression.
+    fn expr_span(&self, tokens: &[(usize, &str)]) -> Option<usize> {
+        let (_, token) = tokens.first()?;
+        match self.fold(token).as_str() {
+            PLUS | MINUS | TIMES | DIVIDE | EQ | NE | LT | GT | AND | OR => {
+                let left = self.expr_span(&tokens[1..])?;
+                let right = self.expr_span(&tokens[1 + left..])?;
+                Some(1 + left + right)
+            }
+            PALETTE | TOWARDS | DISTANCE => Some(1 + self.expr_span(&tokens[1..])?),
+            LIST_START => {
+                let mut depth = 0;
+                for (i, (_, token)) in tokens.iter().enumerate() {
+                    match *token {
+                        LIST_START => depth += 1,
+                        LIST_END => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        return Some(i + 1);
+                    }
+                }
+                None
+            }
+            t if Self::is_expr_start(t) => Some(1),
+            _ => None,
+        }
+    }
+
+    fn is_complete_expr(&self, code: &str) -> bool {
+        let tokens = Self::tokenize(code);
+        !tokens.is_empty() && self.expr_span(&tokens) == Some(tokens.len())
+    }
+
+    /// Split code into whitespace separated tokens with their offsets,
+    /// list brackets are always tokens of their own.
+    fn tokenize(code: &str) -> Vec<(usize, &str)> {
+        let mut tokens = Vec::new();
+        let mut start = None;
+        for (pos, ch) in code.char_indices() {
+            if ch.is_whitespace() || ch == '[' || ch == ']' {
+                if let Some(start) = start.take() {
+                    tokens.push((start, &code[start..pos]));
+                }
+                if !ch.is_whitespace() {
+                    tokens.push((pos, &code[pos..pos + 1]));
+                }
+            } else if start.is_none() {
+                start = Some(pos);
+            }
+        }
+        if let Some(start) = start {
+            tokens.push((start, &code[start..]));
+        }
+        tokens
+    }
+
+    fn is_expr_start(token: &str) -> bool {
+        token.starts_with('"')
+            || token.starts_with(':')
+            || matches!(
+                token,
+                XCOR | YCOR
+                    | HEADING
+                    | POS
+                    | COLOR
+                    | PENCOLOR
+                    | BACKGROUND
+                    | WHO
+                    | PENDOWNP
+                    | PENSIZE
+                    | SHOWNP
+                    | TOWARDS
+                    | DISTANCE
+                    | CANVASWIDTH
+                    | CANVASHEIGHT
+                    | BOUNDS
+                    | PALETTE
+                    | LIST_START
+                    | PLUS
+                    | MINUS
+                    | TIMES
+                    | DIVIDE
+                    | EQ
+                    | NE
+                    | LT
+                    | GT
+                    | AND
+                    | OR
+            )
+    }
+
     fn get_terminator(token: &str) -> &str {
         match token {
             PENUP | PENDOWN | FORWARD | BACK | LEFT | RIGHT | SETX | SETY | SETPENCOLOR | TURN
-            | SETHEADING | MAKE | END | ADDASSIGN => "\n",
+            | SETHEADING | MAKE | END | ADDASSIGN | SUBASSIGN | MULASSIGN | DIVASSIGN => "\n",
             TO => "END",
-            IF | WHILE => "]",
+            IF | WHILE | ASK => "]",
             _ => "\n",
         }
     }
@@ -311,36 +869,58 @@ impl LogoInterpreter {
         if result.len() != 2 {
             return Err(format!("invalid make statement: {}", expr));
         }
-        let var_name = result[0].to_string();
+        let var_name = self.fold(&result[0]);
         let val = result[1].to_string();
         let mut vt = self.var_table.lock().map_err(|e| e.to_string())?;
         vt.insert(var_name, val);
         Ok(())
     }
 
-    fn evaluate_add_assign_statement(
+    fn evaluate_compound_assign_statement(
         &mut self,
+        token: &str,
         expr: String,
         runner: &LogoRunner,
     ) -> Result<(), String> {
         let result = self.evaluate_expr(&expr, runner)?;
         if result.len() != 2 {
-            return Err(format!("invalid addassign statement: {}", expr));
+            return Err(format!(
+                "invalid {} statement: {}",
+                token.to_lowercase(),
+                expr
+            ));
         }
-        let var_name = result[0].to_string();
+        let var_name = self.fold(&result[0]);
         let val: String = result[1].to_string();
+        // assign to whichever scope the variable is resolved from, same order as `:var` reads
         let mut vt = self.var_table.lock().map_err(|e| e.to_string())?;
-        let old_val = vt
-            .get(&var_name)
-            .ok_or(format!("variable not found:{} ", var_name))?
-            .to_string();
-        let new_val = old_val
+        let mut at = self.arg_vars_table.lock().map_err(|e| e.to_string())?;
+        let scope = if at.contains_key(&var_name) {
+            &mut *at
+        } else if vt.contains_key(&var_name) {
+            &mut *vt
+        } else {
+            return Err(format!("variable not found: {}", var_name));
+        };
+        let old_val = scope[&var_name]
+            .parse::<i32>()
+            .map_err(|_| format!("invalid numeric value: {}", scope[&var_name]))?;
+        let val = val
             .parse::<i32>()
-            .map_err(|_| format!("invalid numeric value: {}", old_val))?
-            + val
-                .parse::<i32>()
-                .map_err(|_| format!("invalid numeric value: {}", val))?;
-        vt.insert(var_name, new_val.to_string());
+            .map_err(|_| format!("invalid numeric value: {}", val))?;
+        let new_val = match token {
+            ADDASSIGN => old_val + val,
+            SUBASSIGN => old_val - val,
+            MULASSIGN => old_val * val,
+            DIVASSIGN => {
+                if val == 0 {
+                    return Err(format!("division by zero: {}", expr));
+                }
+                old_val / val
+            }
+            _ => unreachable!(),
+        };
+        scope.insert(var_name, new_val.to_string());
         Ok(())
     }
 
@@ -348,14 +928,19 @@ impl LogoInterpreter {
         &self,
         token: &str,
         expr: String,
+        expr_start: usize,
         runner: &mut LogoRunner,
     ) -> Result<(), String> {
         let oneshot = token == IF;
-        if let Some((mut cond_expr, body)) = expr.trim().split_once("\n") {
-            cond_expr = cond_expr
-                .trim()
-                .strip_suffix("[")
-                .ok_or(format!("invalid conditional statement: {}", cond_expr))?;
+        let block_offset = |block: &str| self.source_offset + expr_start + offset_in(&expr, block);
+        let expr = expr.trim();
+        let tokens = Self::tokenize(expr);
+        let block_start = self
+            .expr_span(&tokens)
+            .and_then(|span| tokens.get(span))
+            .filter(|(_, token)| *token == LIST_START);
+        if let Some((pos, _)) = block_start {
+            let (cond_expr, body) = (&expr[..*pos], &expr[pos + 1..]);
             let mut count = 0;
             loop {
                 if count > 10 {
@@ -366,15 +951,17 @@ impl LogoInterpreter {
                 if result.len() != 1 {
                     return Err(format!("invalid conditional expression: {:?}", result));
                 }
-                if result.iter().next().unwrap() == TRUE {
+                if self.fold_boolean(&result[0]) == TRUE {
                     // condition is true, execute the body
-                    if let Some(body_code) = body.trim().strip_suffix("]") {
+                    if let Some(body_code) = body.trim().strip_suffix(LIST_END) {
                         let mut interpreter = LogoInterpreter::new(
                             body_code.to_string(),
                             self.var_table.clone(),
                             self.procedure_table.clone(),
                             self.arg_vars_table.clone(),
-                        );
+                            self.options,
+                        )
+                        .with_source_offset(block_offset(body_code));
                         interpreter.interpret(runner)?;
                     }
                 } else {
@@ -385,34 +972,201 @@ impl LogoInterpreter {
                 }
             }
         }
-        return Err(format!("invalid conditional statement: {}", expr));
+        Err(format!("invalid conditional statement: {}", expr))
+    }
+
+    /// Run a block with another turtle, commands after it go to the previous one again.
+    fn evaluate_ask_statement(
+        &self,
+        expr: String,
+        expr_start: usize,
+        runner: &mut LogoRunner,
+    ) -> Result<(), String> {
+        let block_offset = |block: &str| self.source_offset + expr_start + offset_in(&expr, block);
+        let expr = expr.trim();
+        let tokens = Self::tokenize(expr);
+        let block_start = self
+            .expr_span(&tokens)
+            .and_then(|span| tokens.get(span))
+            .filter(|(_, token)| *token == LIST_START);
+        let Some((pos, _)) = block_start else {
+            return Err(format!("invalid ask statement: {}", expr));
+        };
+        let (turtle_expr, body) = (&expr[..*pos], &expr[pos + 1..]);
+        let result = self.evaluate_expr(turtle_expr, runner)?;
+        if result.len() != 1 {
+            return Err(format!("invalid argument: {}", turtle_expr));
+        }
+        let turtle = result[0]
+            .parse::<usize>()
+            .map_err(|_| format!("no such turtle: {}", result[0]))?;
+        let body_code = body.trim().strip_suffix(LIST_END).unwrap_or(body);
+        let previous = runner.who();
+        runner.tell(turtle)?;
+        let mut interpreter = LogoInterpreter::new(
+            body_code.to_string(),
+            self.var_table.clone(),
+            self.procedure_table.clone(),
+            self.arg_vars_table.clone(),
+            self.options,
+        )
+        .with_source_offset(block_offset(body_code));
+        let result = interpreter.interpret(runner);
+        runner.tell(previous)?;
+        result
     }
 
     fn evaluate_procedure_definition(
         &mut self,
         expr: String,
+        expr_start: usize,
         runner: &mut LogoRunner,
     ) -> Result<(), String> {
-        if let Some((definition_expr, body)) = expr.trim().split_once("\n") {
-            if let Some((procedure_name, procedure_expr)) = definition_expr.split_once(" ") {
-                let result: Vec<String> = self.evaluate_expr(procedure_expr, runner)?;
-                if result.len() < 1 {
-                    return Err(format!("invalid procedure definition: {}", expr));
-                }
-                if let Some(body_code) = body.trim().strip_suffix("END") {
-                    let mut pt = self.procedure_table.lock().map_err(|e| e.to_string())?;
-                    pt.insert(
-                        procedure_name.to_string(),
-                        LogoProcedure {
-                            source_code: body_code.to_string(),
-                            args: result,
-                        },
-                    );
-                    return Ok(());
+        if let Some((definition_expr, body)) = expr.trim().split_once('\n') {
+            let definition_expr = Self::strip_comment(definition_expr).trim();
+            let (procedure_name, procedure_expr) = definition_expr
+                .split_once(' ')
+                .unwrap_or((definition_expr, ""));
+            let body = body.trim();
+            let body_end = body.len().saturating_sub(END.len());
+            if body.is_char_boundary(body_end) && self.fold(&body[body_end..]) == END {
+                let body_code = &body[..body_end];
+                let mut procedure = self.parse_procedure_args(procedure_expr, runner)?;
+                procedure.source_code = body_code.to_string();
+                procedure.source_offset =
+                    self.source_offset + expr_start + offset_in(&expr, body_code);
+                let procedure_key = self.fold(procedure_name);
+                if KEYWORDS.contains(&procedure_key.as_str()) {
+                    // primitives are matched first, so the procedure could never be called
+                    return self.redefinition(format!(
+                        "procedure name is a primitive: {}",
+                        procedure_name
+                    ));
+                }
+                for name in procedure.input_names() {
+                    if KEYWORDS.contains(&self.fold(name).as_str()) {
+                        self.redefinition(format!(
+                            "input name of procedure {} is a keyword: {}",
+                            procedure_name, name
+                        ))?;
+                    }
+                }
+                let mut pt = self.procedure_table.lock().map_err(|e| e.to_string())?;
+                if pt.contains_key(&procedure_key) {
+                    self.redefinition(format!("procedure already defined: {}", procedure_name))?;
+                }
+                pt.insert(procedure_key, procedure);
+                return Ok(());
+            }
+        }
+        Err(format!("invalid procedure definition: {}", expr))
+    }
+
+    /// Fail with the given message, or only print it when redefinitions are allowed.
+    fn redefinition(&self, message: String) -> Result<(), String> {
+        if !self.options.warn_redefinitions {
+            return Err(message);
+        }
+        eprintln!("warning: {}", message);
+        Ok(())
+    }
+
+    /// Parse the inputs of a procedure definition.
+    /// A `:name` always names the input itself, even when a variable of that name exists,
+    /// other plain inputs are evaluated at definition time. `[:name default]` declares an
+    /// optional input whose default expression is evaluated at call time, and a trailing
+    /// `[:name]` collects the remaining arguments as a list.
+    fn parse_procedure_args(
+        &self,
+        procedure_expr: &str,
+        runner: &LogoRunner,
+    ) -> Result<LogoProcedure, String> {
+        let mut procedure = LogoProcedure {
+            source_code: String::new(),
+            source_offset: 0,
+            args: Vec::new(),
+            optional_args: Vec::new(),
+            rest_arg: None,
+        };
+        let mut pending = Vec::new();
+        let mut group: Option<Vec<&str>> = None;
+        let procedure_expr = procedure_expr.replace('[', " [ ").replace(']', " ] ");
+        for item in procedure_expr.split_whitespace() {
+            match (item, group.as_mut()) {
+                ("[", None) => {
+                    self.push_required_args(&mut procedure, &mut pending, runner)?;
+                    group = Some(Vec::new());
                 }
+                ("]", Some(items)) => {
+                    if procedure.rest_arg.is_some() {
+                        return Err(format!(
+                            "rest input must be the last input: {}",
+                            procedure_expr.trim()
+                        ));
+                    }
+                    let (name, default) = items
+                        .split_first()
+                        .ok_or(format!("empty optional input: {}", procedure_expr.trim()))?;
+                    let name = Self::input_name(name)?;
+                    if default.is_empty() {
+                        procedure.rest_arg = Some(name);
+                    } else {
+                        procedure.optional_args.push((name, default.join(" ")));
+                    }
+                    group = None;
+                }
+                ("[", Some(_)) | ("]", None) => {
+                    return Err(format!(
+                        "invalid procedure inputs: {}",
+                        procedure_expr.trim()
+                    ));
+                }
+                (item, Some(items)) => items.push(item),
+                (item, None) => pending.push(item),
             }
         }
-        return Err(format!("invalid procedure definition: {}", expr));
+        if group.is_some() {
+            return Err(format!(
+                "unterminated optional input: {}",
+                procedure_expr.trim()
+            ));
+        }
+        self.push_required_args(&mut procedure, &mut pending, runner)?;
+        Ok(procedure)
+    }
+
+    fn push_required_args(
+        &self,
+        procedure: &mut LogoProcedure,
+        pending: &mut Vec<&str>,
+        runner: &LogoRunner,
+    ) -> Result<(), String> {
+        if pending.is_empty() {
+            return Ok(());
+        }
+        if !procedure.optional_args.is_empty() || procedure.rest_arg.is_some() {
+            return Err(format!(
+                "required input after optional input: {}",
+                pending.join(" ")
+            ));
+        }
+        let mut items = Vec::new();
+        for item in pending.drain(..) {
+            match item.strip_prefix(':') {
+                Some(name) => items.push(format!("\"{}", name)),
+                None => items.push(item.to_string()),
+            }
+        }
+        let args = self.evaluate_expr(&items.join(" "), runner)?;
+        procedure.args.extend(args);
+        Ok(())
+    }
+
+    fn input_name(item: &str) -> Result<String, String> {
+        match item.strip_prefix([':', '"']) {
+            Some(name) if !name.is_empty() => Ok(name.to_string()),
+            _ => Err(format!("invalid procedure input: {}", item)),
+        }
     }
 
     fn find_evaluate_procedure(
@@ -422,27 +1176,120 @@ impl LogoInterpreter {
         runner: &mut LogoRunner,
     ) -> Result<(), String> {
         let pt = self.procedure_table.lock().map_err(|e| e.to_string())?;
-        if let Some(procedure) = pt.get(token) {
+        if let Some(procedure) = pt.get(&self.fold(token)) {
             let arg_vars = self.evaluate_expr(&expr, runner)?;
-            if procedure.args.len() != arg_vars.len() {
+            let max_args = procedure.args.len() + procedure.optional_args.len();
+            if arg_vars.len() < procedure.args.len()
+                || (procedure.rest_arg.is_none() && arg_vars.len() > max_args)
+            {
                 return Err(format!(
-                    "invalid number of arguments for procedure: {}",
-                    token
+                    "invalid number of arguments for procedure: {}, expected {}, got {}",
+                    token,
+                    procedure.signature(token),
+                    arg_vars.len()
                 ));
             }
+            let procedure = procedure.clone();
+            drop(pt);
+            let mut arg_vars = arg_vars.into_iter();
             let mut arg_vars_table = HashMap::new();
-            arg_vars.iter().enumerate().for_each(|(i, v)| {
-                arg_vars_table.insert(procedure.args[i].to_string(), v.to_string());
-            });
+            for name in procedure.args.iter() {
+                arg_vars_table.insert(self.fold(name), arg_vars.next().unwrap());
+            }
+            let arg_vars_table = Arc::new(Mutex::new(arg_vars_table));
             let mut interpreter = LogoInterpreter::new(
                 procedure.source_code.to_string(),
                 self.var_table.clone(),
                 self.procedure_table.clone(),
-                arg_vars_table,
-            );
-            drop(pt);
+                arg_vars_table.clone(),
+                self.options,
+            )
+            .with_source_offset(procedure.source_offset);
+            for (name, default) in procedure.optional_args.iter() {
+                let val = match arg_vars.next() {
+                    Some(val) => val,
+                    // a bare number or word is the value itself
+                    None if !default.contains(char::is_whitespace)
+                        && !Self::is_expr_start(&self.fold(default)) =>
+                    {
+                        default.to_string()
+                    }
+                    None => {
+                        // defaults may refer to the inputs bound before them
+                        let result = interpreter.evaluate_expr(default, runner)?;
+                        if result.len() != 1 {
+                            return Err(format!("invalid default value: {}", default));
+                        }
+                        result[0].to_string()
+                    }
+                };
+                let mut at = arg_vars_table.lock().map_err(|e| e.to_string())?;
+                at.insert(self.fold(name), val);
+            }
+            if let Some(name) = procedure.rest_arg.as_ref() {
+                let rest = arg_vars.collect::<Vec<String>>();
+                let mut at = arg_vars_table.lock().map_err(|e| e.to_string())?;
+                at.insert(self.fold(name), format!("[{}]", rest.join(" ")));
+            }
             return interpreter.interpret(runner);
         }
-        return Err(format!("unknown procedure: {}", token));
+        Err(format!("unknown procedure: {}", token))
+    }
+}
+
+impl LogoProcedure {
+    fn input_names(&self) -> impl Iterator<Item = &str> {
+        self.args
+            .iter()
+            .chain(self.optional_args.iter().map(|(arg, _)| arg))
+            .chain(self.rest_arg.iter())
+            .map(|arg| arg.as_str())
+    }
+
+    /// Render the procedure header the way it would be written after `TO`.
+    fn signature(&self, name: &str) -> String {
+        let mut signature = vec![name.to_string()];
+        signature.extend(self.args.iter().map(|arg| format!(":{}", arg)));
+        signature.extend(
+            self.optional_args
+                .iter()
+                .map(|(arg, default)| format!("[:{} {}]", arg, default)),
+        );
+        if let Some(arg) = self.rest_arg.as_ref() {
+            signature.push(format!("[:{}]", arg));
+        }
+        signature.join(" ")
+    }
+}
+
+/// Split a list value like `[1 [2 3] 4]` into its top level items,
+/// `None` if the value isn't a list.
+pub fn list_items(val: &str) -> Option<Vec<String>> {
+    let inner = val.strip_prefix('[')?.strip_suffix(']')?;
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut depth = 0;
+    for ch in inner.chars() {
+        match ch {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            _ => {}
+        }
+        if ch.is_whitespace() && depth == 0 {
+            if !item.is_empty() {
+                items.push(std::mem::take(&mut item));
+            }
+        } else {
+            item.push(ch);
+        }
     }
+    if !item.is_empty() {
+        items.push(item);
+    }
+    Some(items)
+}
+
+/// Byte offset of `part` in `whole`, which it was sliced from.
+fn offset_in(whole: &str, part: &str) -> usize {
+    part.as_ptr() as usize - whole.as_ptr() as usize
 }
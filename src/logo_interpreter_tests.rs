@@ -1,6 +1,40 @@
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use crate::{logo_interpreter, logo_runner};
+use crate::{logo_canvas, logo_color, logo_display_list, logo_interpreter, logo_runner};
+
+/// Save the drawing to a file of its own in the temp dir.
+fn save_as(runner: logo_runner::LogoRunner, extension: &str) -> PathBuf {
+    static SAVED: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "rslogo_test_{}_{}.{}",
+        std::process::id(),
+        SAVED.fetch_add(1, Ordering::Relaxed),
+        extension
+    ));
+    runner.save(&path).expect("Unable to save image");
+    path
+}
+
+fn save_svg(runner: logo_runner::LogoRunner) -> String {
+    std::fs::read_to_string(save_as(runner, "svg")).unwrap()
+}
+
+/// Run a program on a fresh canvas and return the svg it draws.
+fn render_svg(content: &str, width: u32, height: u32) -> String {
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(width, height).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    save_svg(runner)
+}
+
+/// The path data of every stroked path in the svg, in drawing order.
+fn strokes(svg: &str) -> Vec<&str> {
+    svg.split("<path fill=\"none\"")
+        .skip(1)
+        .filter_map(|path| path.split(" d=\"").nth(1)?.split('"').next())
+        .collect()
+}
 
 #[test]
 fn test_file() {
@@ -8,9 +42,1091 @@ fn test_file() {
     let content = std::fs::read_to_string(file_path).expect("Unable to read logo file");
 
     let mut interpreter = logo_interpreter::LogoInterpreter::default(content);
-    let mut runner = logo_runner::LogoRunner::new(300, 300);
+    let mut runner = logo_runner::LogoRunner::new(300, 300).unwrap();
     println!("result: {:#?}", interpreter.interpret(&mut runner));
     runner
         .save(&PathBuf::from("test.png"))
         .expect("Unable to save image");
 }
+
+#[test]
+fn test_compound_assign() {
+    let content = r#"
+MAKE "dist "20
+SUBASSIGN "dist "5
+MULASSIGN "dist "4
+DIVASSIGN "dist "3
+FORWARD :dist
+"#;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(300, 300).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!(runner.get_pos_y(), 130);
+
+    let mut interpreter =
+        logo_interpreter::LogoInterpreter::default("MAKE \"x \"1\nDIVASSIGN \"x \"0\n".to_string());
+    assert!(interpreter.interpret(&mut runner).is_err());
+}
+
+#[test]
+fn test_compound_assign_on_procedure_args() {
+    let content = r#"
+TO Walk "len
+   ADDASSIGN "len "10
+   WHILE GT :len "0 [
+      FORWARD "1
+      SUBASSIGN "len "5
+   ]
+END
+
+Walk "20
+"#;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(300, 300).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!(runner.get_pos_y(), 144);
+}
+
+#[test]
+fn test_procedure_optional_and_rest_args() {
+    let content = r#"
+TO Step "len [:times "2] [:more]
+   WHILE GT :times "0 [
+      FORWARD :len
+      SUBASSIGN "times "1
+   ]
+END
+
+Step "10
+Step "5 "1
+Step "1 "1 "2 "3
+"#;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(300, 300).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!(runner.get_pos_y(), 124);
+
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(
+        "TO Poly \"sides [:size \"50]\nFORWARD :size\nEND\nPoly\n".to_string(),
+    );
+    let err = interpreter.interpret(&mut runner).unwrap_err();
+    assert!(err.contains("Poly :sides [:size \"50]"), "{}", err);
+}
+
+#[test]
+fn test_procedure_inputs_by_name() {
+    let content = r#"
+MAKE "sides "size
+TO POLY :sides [:size 50] [:more]
+   PENUP
+   FORWARD :size
+   BACK :sides
+END
+
+POLY "4
+POLY "3 "10
+POLY "1 "2 "3 "4
+"#;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(300, 300).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!(runner.get_pos_y(), 150 - 50 + 4 - 10 + 3 - 2 + 1);
+}
+
+#[test]
+fn test_procedure_redefinition() {
+    let run = |content: &str, options: logo_interpreter::LogoOptions| {
+        let mut interpreter =
+            logo_interpreter::LogoInterpreter::default(content.to_string()).with_options(options);
+        let mut runner = logo_runner::LogoRunner::new(300, 300).unwrap();
+        interpreter.interpret(&mut runner).map(|_| runner.get_pos_y())
+    };
+    let strict = logo_interpreter::LogoOptions::default();
+    let lenient = logo_interpreter::LogoOptions {
+        warn_redefinitions: true,
+        ..Default::default()
+    };
+
+    let shadowed = "TO FORWARD \"len\nBACK :len\nEND\nFORWARD \"10\n";
+    assert!(run(shadowed, strict).unwrap_err().contains("primitive"));
+    assert_eq!(run(shadowed, lenient), Ok(140));
+
+    let duplicate = "TO Up\nFORWARD \"10\nEND\nTO Up\nFORWARD \"20\nEND\nUp\n";
+    assert!(run(duplicate, strict).unwrap_err().contains("already defined"));
+    assert_eq!(run(duplicate, lenient), Ok(130));
+
+    let keyword_input = "TO Up \"HEADING\nFORWARD :HEADING\nEND\nUp \"5\n";
+    assert!(run(keyword_input, strict).unwrap_err().contains("keyword"));
+    assert_eq!(run(keyword_input, lenient), Ok(145));
+}
+
+#[test]
+fn test_case_insensitive_names() {
+    let content = r#"
+to step "Len
+   forward :len
+End
+make "Dist "10
+addassign "dist "5
+Step :DIST
+if eq "true "TRUE [
+   SetHeading "90
+]
+"#;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(300, 300).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!(runner.get_pos_y(), 135);
+    assert_eq!(runner.get_direction(), 90);
+
+    let options = logo_interpreter::LogoOptions {
+        case_sensitive: true,
+        ..Default::default()
+    };
+    let mut interpreter = logo_interpreter::LogoInterpreter::default("forward \"10\n".to_string())
+        .with_options(options);
+    assert!(interpreter.interpret(&mut runner).is_err());
+}
+
+#[test]
+fn test_end_inside_names() {
+    let content = r#"
+to sq "legend
+   penup
+   forward :legend
+   make "send "5
+   make "extend :send
+   back :extend
+end
+sq "10
+"#;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(300, 300).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!(runner.get_pos_y(), 145);
+}
+
+#[test]
+fn test_trailing_comments_and_multi_statement_lines() {
+    let content = r#"
+MAKE "len "10 // [unbalanced brackets in comments are ignored
+PENDOWN FORWARD :len TURN "90 // draw the first side
+TO Side "dist // one side of the shape
+   FORWARD :dist RIGHT "5
+END
+Side "20 Side + :len "10
+IF EQ :len "10 [ SETHEADING "90 FORWARD "3 ] BACK "1
+"#;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(300, 300).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!(runner.get_pos_x(), 190);
+    assert_eq!(runner.get_pos_y(), 152);
+    assert_eq!(runner.get_direction(), 180);
+
+    let mut interpreter =
+        logo_interpreter::LogoInterpreter::default("FORWARD \"10 \"20\n".to_string());
+    let err = interpreter.interpret(&mut runner).unwrap_err();
+    assert!(err.contains("unexpected argument"), "{}", err);
+}
+
+#[test]
+fn test_pen_size_and_pattern() {
+    let content = r#"
+PENDOWN
+SETPENSIZE "4
+SETPENPATTERN "dashed
+SETLINECAP "round
+FORWARD "20
+SETPENSIZE "1 SETPENPATTERN "solid SETLINECAP "butt
+FORWARD "20
+"#;
+    let svg = render_svg(content, 100, 100);
+    assert!(svg.contains(
+        r##"stroke="#ffffff" stroke-width="4" stroke-linecap="round" stroke-dasharray="16 12" d="M 50 50 L 50 30""##
+    ));
+    assert!(svg.contains(r##"<path fill="none" stroke="#ffffff" d="M 50 30 L 50 10"/>"##));
+    assert_eq!(strokes(&svg), ["M 50 50 L 50 30", "M 50 30 L 50 10"]);
+
+    let mut interpreter =
+        logo_interpreter::LogoInterpreter::default("SETPENPATTERN \"wavy\n".to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    assert!(interpreter.interpret(&mut runner).is_err());
+    let mut interpreter =
+        logo_interpreter::LogoInterpreter::default("SETPENSIZE \"0\n".to_string());
+    assert!(interpreter.interpret(&mut runner).is_err());
+
+    assert!(logo_runner::LogoRunner::new(0, 50).is_err());
+}
+
+#[test]
+fn test_rgb_pen_colors() {
+    let content = r##"
+PENDOWN
+SETPENCOLOR [255 136 0]
+FORWARD "10
+SETPENCOLOR "#ff880080
+FORWARD "10
+MAKE "rgb PENCOLOR
+SETPENCOLOR "Orange
+FORWARD "10
+SETPENCOLOR [0 0 255]
+IF EQ COLOR "1 [ SETPENCOLOR :rgb ]
+IF EQ COLOR [255 136 0 128] [ FORWARD "10 ]
+"##;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!(runner.get_color_index(), None);
+    let svg = save_svg(runner);
+    assert!(svg.contains(r##"stroke="#ff8800" d="M 50 50 L 50 40""##));
+    assert!(svg.contains(r##"stroke="#ff8800" stroke-opacity="0.502" d="M 50 40 L 50 30""##));
+    assert!(svg.contains(r##"stroke="#ffa500" d="M 50 30 L 50 20""##));
+    assert!(svg.contains(r##"stroke-opacity="0.502" d="M 50 20 L 50 10""##));
+    assert_eq!(strokes(&svg).len(), 4);
+
+    let mut interpreter =
+        logo_interpreter::LogoInterpreter::default("SETPENCOLOR [255 0]\n".to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    assert!(interpreter.interpret(&mut runner).is_err());
+}
+
+#[test]
+fn test_set_palette() {
+    let content = r##"
+PENDOWN
+FORWARD "10
+SETPALETTE "2 [10 20 30]
+SETPENCOLOR "2
+FORWARD "10
+SETPALETTE "2 "#abcdef
+MAKE "entry PALETTE "2
+SETPENCOLOR :entry
+IF EQ COLOR "2 [ FORWARD "10 ]
+"##;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    let palette = "// themed\n7 [255 136 0]\n20 #102030 // new index\n";
+    for (index, color) in logo_color::parse_palette(palette).unwrap() {
+        runner.set_palette(index, color).unwrap();
+    }
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!(runner.get_color_index(), Some(2));
+    assert_eq!(runner.get_palette(20).unwrap().to_hex(), "#102030");
+    let svg = save_svg(runner);
+    assert!(svg.contains(r##"stroke="#ff8800" d="M 50 50 L 50 40""##));
+    assert!(svg.contains(r##"stroke="#0a141e" d="M 50 40 L 50 30""##));
+    assert!(svg.contains(r##"stroke="#abcdef" d="M 50 30 L 50 20""##));
+    assert_eq!(strokes(&svg).len(), 3);
+
+    assert!(logo_color::parse_palette("7 [255 0]\n").is_err());
+    let mut interpreter =
+        logo_interpreter::LogoInterpreter::default("SETPENCOLOR \"30\n".to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    assert!(interpreter.interpret(&mut runner).is_err());
+}
+
+#[test]
+fn test_fill() {
+    let content = r##"
+SETFILLCOLOR "red
+BEGINFILL
+PENDOWN
+FORWARD "20 TURN "90 FORWARD "20 TURN "90 FORWARD "20
+ENDFILL
+"##;
+    let svg = render_svg(content, 100, 100);
+    let fill = svg
+        .find(r##"<path fill="#ff0000" stroke="none" d="M 50 50 L 50 30 L 70 30 L 70 50 Z"/>"##)
+        .unwrap();
+    assert!(fill < svg.find(r##"stroke="#ffffff""##).unwrap());
+    assert_eq!(
+        strokes(&svg),
+        ["M 50 50 L 50 30", "M 50 30 L 70 30", "M 70 30 L 70 50"]
+    );
+
+    // flood fill the inside of a closed outline
+    let content = r##"
+PENDOWN
+FORWARD "20 TURN "90 FORWARD "20 TURN "90 FORWARD "20 TURN "90 FORWARD "20
+PENUP BACK "10 TURN "90 FORWARD "10
+SETFILLCOLOR "2
+FILL
+"##;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    let path = save_as(runner, "png");
+    let pixmap = tiny_skia::Pixmap::load_png(&path).unwrap();
+    let inside = pixmap.pixel(60, 40).unwrap();
+    assert_eq!((inside.red(), inside.green(), inside.blue()), (0, 255, 255));
+    let outside = pixmap.pixel(10, 10).unwrap();
+    assert_eq!((outside.red(), outside.green(), outside.blue()), (0, 0, 0));
+
+    let mut interpreter = logo_interpreter::LogoInterpreter::default("ENDFILL\n".to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    assert!(interpreter.interpret(&mut runner).is_err());
+}
+
+#[test]
+fn test_arc_and_circle() {
+    let content = r##"
+PENDOWN
+ARC "90 "10
+CIRCLE "10
+ARC "-90 "10
+"##;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!((runner.get_pos_x(), runner.get_pos_y()), (70, 30));
+    assert_eq!(runner.get_direction(), 0);
+    let svg = save_svg(runner);
+    assert!(
+        svg.contains(r##"<path fill="none" stroke="#ffffff" d="M 50 50 A 10 10 0 0 1 60 40"/>"##)
+    );
+    assert!(svg.contains(r##"<circle fill="none" stroke="#ffffff" cx="60" cy="50" r="10"/>"##));
+    assert_eq!(
+        strokes(&svg),
+        ["M 50 50 A 10 10 0 0 1 60 40", "M 60 40 A 10 10 0 0 0 70 30"]
+    );
+
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    let path = save_as(runner, "png");
+    let pixmap = tiny_skia::Pixmap::load_png(&path).unwrap();
+    assert!(pixmap.pixel(69, 50).unwrap().red() > 0);
+    assert_eq!(pixmap.pixel(60, 50).unwrap().red(), 0);
+}
+
+#[test]
+fn test_list_words_are_literal() {
+    let content = r##"
+LABEL [black and white]
+LABEL [pen color]
+LABEL [the pos is + 1]
+MAKE "n "3
+LABEL [:n "4]
+"##;
+    let svg = render_svg(content, 100, 100);
+    for text in [
+        "black and white",
+        "pen color",
+        "the pos is + 1",
+        ":n &quot;4",
+    ] {
+        let label = format!(
+            r##"<text x="50" y="50" font-family="sans-serif" font-size="12" fill="#ffffff" transform="rotate(-90 50 50)">{}</text>"##,
+            text
+        );
+        assert!(svg.contains(&label), "{}", text);
+    }
+
+    let mut interpreter =
+        logo_interpreter::LogoInterpreter::default("MAKE \"n \"3\nSETPOS [:n \"4]\n".to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    assert!(interpreter.interpret(&mut runner).is_err());
+}
+
+#[test]
+fn test_label() {
+    let content = r##"
+SETLABELHEIGHT "20
+SETLABELFONT [DejaVu Sans]
+SETHEADING "90
+LABEL [x < y & z]
+SETHEADING "90
+SETPENCOLOR "red
+LABEL "up
+"##;
+    let svg = render_svg(content, 100, 100);
+    assert!(svg.contains(
+        r##"<text x="50" y="50" font-family="DejaVu Sans" font-size="20" fill="#ffffff">x &lt; y &amp; z</text>"##
+    ));
+    assert!(svg.contains(r##"fill="#ff0000" transform="rotate(90 50 50)">up</text>"##));
+
+    let mut interpreter =
+        logo_interpreter::LogoInterpreter::default("SETHEADING \"90\nLABEL \"Logo\n".to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    let path = save_as(runner, "png");
+    let pixmap = tiny_skia::Pixmap::load_png(&path).unwrap();
+    // the glyphs sit on the baseline to the right of the turtle
+    let drawn = |x0: u32, y0: u32, x1: u32, y1: u32| {
+        (x0..x1).any(|x| (y0..y1).any(|y| pixmap.pixel(x, y).unwrap().red() > 0))
+    };
+    assert!(drawn(50, 40, 75, 50));
+    assert!(!drawn(0, 0, 100, 35));
+    assert!(!drawn(0, 0, 48, 100));
+}
+
+#[test]
+fn test_non_ascii_label() {
+    let content = r##"
+LABEL "héllo
+LABEL "café // note
+TO Größe "länge
+    FORWARD :länge
+END
+Größe "7
+"##;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!(runner.get_pos_y(), 43);
+    let svg = save_svg(runner);
+    assert!(svg.contains(
+        r##"<text x="50" y="50" font-family="sans-serif" font-size="12" fill="#ffffff" transform="rotate(-90 50 50)">héllo</text>"##
+    ));
+    assert!(svg.contains(
+        r##"<text x="50" y="50" font-family="sans-serif" font-size="12" fill="#ffffff" transform="rotate(-90 50 50)">café</text>"##
+    ));
+}
+
+#[test]
+fn test_home_clear_and_setpos() {
+    let content = r##"
+PENDOWN
+FORWARD "10
+CLEAN
+SETXY "20 "30
+TURN "45
+FORWARD "0
+MAKE "p POS
+HOME
+SETPOS :p
+SETXY + "1 "1 "5
+FORWARD "5
+"##;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!((runner.get_pos_x(), runner.get_pos_y()), (2, 0));
+    assert_eq!(runner.get_direction(), 0);
+    let svg = save_svg(runner);
+    // CLEAN dropped the first line
+    assert_eq!(strokes(&svg), ["M 20 30 L 20 30", "M 2 5 L 2 0"]);
+
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(
+        "PENDOWN FORWARD \"10 CLEARSCREEN\n".to_string(),
+    );
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!((runner.get_pos_x(), runner.get_pos_y()), (50, 50));
+    let mut interpreter =
+        logo_interpreter::LogoInterpreter::default("SETPOS [1 2 3]\n".to_string());
+    assert!(interpreter.interpret(&mut runner).is_err());
+}
+
+#[test]
+fn test_logo_coordinates() {
+    let content = r##"
+SETX "10
+SETY "20
+PENDOWN
+FORWARD "5
+MAKE "x XCOR
+SETXY :x "-5
+"##;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100)
+        .unwrap()
+        .with_coordinates(logo_runner::Coordinates::Logo);
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!((runner.get_pos_x(), runner.get_pos_y()), (10, -5));
+    let svg = save_svg(runner);
+    assert_eq!(strokes(&svg), ["M 60 30 L 60 25"]);
+}
+
+#[test]
+fn test_boundary_modes() {
+    let content = r##"
+WRAP
+PENDOWN
+FORWARD "70
+TURN "90
+FORWARD "130
+"##;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!((runner.get_pos_x(), runner.get_pos_y()), (80, 80));
+    let svg = save_svg(runner);
+    assert_eq!(
+        strokes(&svg),
+        [
+            "M 50 50 L 50 0",
+            "M 50 100 L 50 80",
+            "M 50 80 L 100 80",
+            "M 0 80 L 80 80"
+        ]
+    );
+
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    let mut interpreter =
+        logo_interpreter::LogoInterpreter::default("FENCE FORWARD \"50 FORWARD \"1\n".to_string());
+    let err = interpreter.interpret(&mut runner).unwrap_err();
+    assert!(err.contains("out of bounds"), "{}", err);
+    assert_eq!(runner.get_pos_y(), 0);
+    let mut interpreter =
+        logo_interpreter::LogoInterpreter::default("WINDOW FORWARD \"50\n".to_string());
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!(runner.get_pos_y(), -50);
+}
+
+#[test]
+fn test_pen_modes() {
+    let content = r##"
+PENDOWN
+SETPENSIZE "3
+FORWARD "20
+PENERASE
+BACK "10
+PENREVERSE
+SETHEADING "90
+FORWARD "20
+PENPAINT
+"##;
+    let svg = render_svg(content, 100, 100);
+    assert!(svg.contains(r##"<g mask="url(#erase0)">"##));
+    assert!(svg.contains(
+        r##"<mask id="erase0" maskUnits="userSpaceOnUse" x="0" y="0" width="100" height="100">"##
+    ));
+    assert!(svg.contains(
+        r##"            <path fill="none" stroke="#000000" stroke-width="3" d="M 50 30 L 50 40"/>"##
+    ));
+    assert!(svg.contains(
+        r##"stroke="#ffffff" stroke-width="3" style="mix-blend-mode:difference" d="M 50 40 L 70 40""##
+    ));
+
+    // reversing the same line twice restores the pixels below
+    let content = r##"
+PENDOWN SETPENSIZE "3 FORWARD "20 BACK "10
+PENREVERSE SETPENCOLOR "red SETHEADING "90 FORWARD "20 BACK "10
+"##;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    let path = save_as(runner, "png");
+    let pixmap = tiny_skia::Pixmap::load_png(&path).unwrap();
+    let rgb = |x, y| {
+        let p = pixmap.pixel(x, y).unwrap();
+        (p.red(), p.green(), p.blue())
+    };
+    assert_eq!(rgb(50, 40), (0, 255, 255));
+    assert_eq!(rgb(55, 40), (255, 0, 0));
+    assert_eq!(rgb(65, 40), (0, 0, 0));
+    assert_eq!(rgb(50, 35), (255, 255, 255));
+
+    // a line reversed while a fill is open stays above the fill
+    let content = r##"
+SETFILLCOLOR "blue PENDOWN BEGINFILL
+FORWARD "20 TURN "90 FORWARD "20 TURN "90 FORWARD "20 TURN "90 FORWARD "20
+PENREVERSE SETPENCOLOR "red SETPENSIZE "3 TURN "135 FORWARD "14
+ENDFILL
+"##;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    let path = save_as(runner, "png");
+    let pixmap = tiny_skia::Pixmap::load_png(&path).unwrap();
+    let rgb = |x, y| {
+        let p = pixmap.pixel(x, y).unwrap();
+        (p.red(), p.green(), p.blue())
+    };
+    assert_eq!(rgb(55, 45), (255, 0, 0));
+    assert_eq!(rgb(65, 45), (0, 0, 255));
+}
+
+#[test]
+fn test_background() {
+    let content = r##"
+PENDOWN
+SETPENSIZE "2
+FORWARD "10
+SETBACKGROUND [255 255 0]
+MAKE "bg BACKGROUND
+PENERASE
+BACK "5
+"##;
+    let svg = render_svg(content, 100, 100);
+    assert!(svg.contains(
+        r##"<path fill="#ffff00" stroke="none" d="M 0 0 L 100 0 L 100 100 L 0 100 Z"/>"##
+    ));
+
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    let path = save_as(runner, "png");
+    let pixmap = tiny_skia::Pixmap::load_png(&path).unwrap();
+    let rgb = |x, y| {
+        let p = pixmap.pixel(x, y).unwrap();
+        (p.red(), p.green(), p.blue(), p.alpha())
+    };
+    assert_eq!(rgb(10, 10), (255, 255, 0, 255));
+    // erasing lets the background through
+    assert_eq!(rgb(50, 42), (255, 255, 0, 255));
+    assert_eq!(rgb(50, 47), (255, 255, 255, 255));
+
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(
+        "SETBACKGROUND \"transparent PENDOWN FORWARD \"10\n".to_string(),
+    );
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    let path = save_as(runner, "png");
+    let pixmap = tiny_skia::Pixmap::load_png(&path).unwrap();
+    assert_eq!(pixmap.pixel(10, 10).unwrap().alpha(), 0);
+}
+
+#[test]
+fn test_multiple_turtles() {
+    let content = r##"
+PENDOWN
+NEWTURTLE
+MAKE "second WHO
+PENDOWN
+TURN "90
+FORWARD "10
+TELL "0
+FORWARD "5
+ASK :second [
+    FORWARD "10
+]
+FORWARD "5
+"##;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!(runner.who(), 0);
+    assert_eq!((runner.get_pos_x(), runner.get_pos_y()), (50, 40));
+    runner.tell(1).unwrap();
+    assert_eq!((runner.get_pos_x(), runner.get_pos_y()), (70, 50));
+    assert_eq!(runner.get_direction(), 90);
+    let svg = save_svg(runner);
+    assert_eq!(
+        strokes(&svg),
+        [
+            "M 50 50 L 60 50",
+            "M 50 50 L 50 45",
+            "M 60 50 L 70 50",
+            "M 50 45 L 50 40"
+        ]
+    );
+
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    let mut interpreter = logo_interpreter::LogoInterpreter::default("TELL \"1\n".to_string());
+    let err = interpreter.interpret(&mut runner).unwrap_err();
+    assert!(err.contains("no such turtle"), "{}", err);
+    let mut interpreter =
+        logo_interpreter::LogoInterpreter::default("NEWTURTLE ASK \"0 [ TELL \"5 ]\n".to_string());
+    assert!(interpreter.interpret(&mut runner).is_err());
+    assert_eq!(runner.who(), 1);
+
+    // the canvas holds one fill, shared by all turtles
+    let content = r##"
+NEWTURTLE TELL "0 PENDOWN BEGINFILL FORWARD "10
+TELL "1 PENDOWN BEGINFILL
+"##;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    let err = interpreter.interpret(&mut runner).unwrap_err();
+    assert!(err.contains("fill already started by turtle 0"), "{}", err);
+    let mut interpreter = logo_interpreter::LogoInterpreter::default("ENDFILL\n".to_string());
+    let err = interpreter.interpret(&mut runner).unwrap_err();
+    assert!(err.contains("fill already started by turtle 0"), "{}", err);
+    let content = r##"
+TELL "0 TURN "90 FORWARD "10 ENDFILL
+TELL "1 SETFILLCOLOR "red BEGINFILL TURN "90 FORWARD "10 TURN "90 FORWARD "10 ENDFILL
+"##;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    interpreter.interpret(&mut runner).unwrap();
+    let svg = save_svg(runner);
+    assert!(svg.contains(r##"<path fill="#ffffff" stroke="none" d="M 50 50 L 50 40 L 60 40 Z"/>"##));
+    assert!(svg.contains(r##"<path fill="#ff0000" stroke="none" d="M 50 50 L 60 50 L 60 60 Z"/>"##));
+}
+
+#[test]
+fn test_push_and_pop_turtle() {
+    let content = r##"
+PENDOWN
+FORWARD "10
+PUSHTURTLE
+TURN "90
+SETPENSIZE "4
+SETPENCOLOR "1
+FORWARD "10
+PENUP
+POPTURTLE
+FORWARD "10
+"##;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!((runner.get_pos_x(), runner.get_pos_y()), (50, 30));
+    assert_eq!(runner.get_direction(), 0);
+    assert_eq!(runner.get_color_index(), Some(7));
+    let svg = save_svg(runner);
+    assert!(svg.contains(r##"stroke="#0000ff" stroke-width="4" d="M 50 40 L 60 40""##));
+    assert!(svg.contains(r##"stroke="#ffffff" d="M 50 40 L 50 30""##));
+    assert_eq!(
+        strokes(&svg),
+        ["M 50 50 L 50 40", "M 50 40 L 60 40", "M 50 40 L 50 30"]
+    );
+
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    let mut interpreter =
+        logo_interpreter::LogoInterpreter::default("PUSHTURTLE POPTURTLE POPTURTLE\n".to_string());
+    let err = interpreter.interpret(&mut runner).unwrap_err();
+    assert!(err.contains("stack is empty"), "{}", err);
+}
+
+#[test]
+fn test_show_turtle_and_stamp() {
+    let content = r##"
+STAMP
+TURN "90
+FORWARD "20
+SETSHAPE [[0 4] [4 0] [0 -4] [-4 0]]
+SHOWTURTLE
+"##;
+    let svg = render_svg(content, 100, 100);
+    assert_eq!(
+        strokes(&svg),
+        [
+            "M 50 40 L 55 55 L 45 55 Z",
+            "M 74 50 L 70 54 L 66 50 L 70 46 Z"
+        ]
+    );
+
+    // the shape is drawn through the transform like the turtle's moves
+    let content = r##"
+ROTATE "90
+STAMP
+SETSCALE "2
+MIRROR
+TURN "90
+STAMP
+"##;
+    let svg = render_svg(content, 100, 100);
+    assert_eq!(
+        strokes(&svg),
+        ["M 60 50 L 45 55 L 45 45 Z", "M 50 30 L 40 60 L 60 60 Z"]
+    );
+
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    let mut interpreter =
+        logo_interpreter::LogoInterpreter::default("SETSHAPE [[0 1] [1 0]]\n".to_string());
+    assert!(interpreter.interpret(&mut runner).is_err());
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(
+        "SHOWTURTLE HIDETURTLE SETSHAPE []\n".to_string(),
+    );
+    interpreter.interpret(&mut runner).unwrap();
+    let svg = save_svg(runner);
+    assert!(strokes(&svg).is_empty());
+}
+
+#[test]
+fn test_queries() {
+    let content = r##"
+MAKE "down PENDOWNP
+PENDOWN
+SETPENSIZE "3
+SHOWTURTLE
+SETPOS [10 0]
+TURN TOWARDS [10 20]
+FORWARD DISTANCE [10 20]
+TURN "90
+FORWARD / CANVASWIDTH "2
+IF AND PENDOWNP SHOWNP [
+    SETPENSIZE + PENSIZE "1
+]
+IF EQ :down "FALSE [
+    MAKE "bounds BOUNDS
+    SETXY + CANVASWIDTH "10 CANVASHEIGHT
+]
+"##;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 60)
+        .unwrap()
+        .with_coordinates(logo_runner::Coordinates::Logo);
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!(runner.get_direction(), 90);
+    assert_eq!(runner.get_pen_size(), 4);
+    assert_eq!((runner.get_pos_x(), runner.get_pos_y()), (110, 60));
+    assert_eq!(runner.bounds(), [-50, -30, 50, 30]);
+    assert_eq!(runner.towards(110, 50), 180);
+    assert_eq!(runner.distance(113, 64), 5);
+    let svg = save_svg(runner);
+    assert_eq!(
+        strokes(&svg),
+        [
+            "M 60 30 L 60 10",
+            "M 60 10 L 110 10",
+            // the turtle shown at its final position
+            "M 170 -30 L 155 -25 L 155 -35 Z"
+        ]
+    );
+}
+
+#[test]
+fn test_transforms() {
+    let content = r##"
+PENDOWN
+PUSHTRANSFORM
+SETSCALE "2
+TRANSLATE "10 "0
+SETPOS [0 0]
+FORWARD "5
+MAKE "y YCOR
+ROTATE "90
+FORWARD :y
+MIRROR
+TURN "90
+FORWARD "5
+POPTRANSFORM
+FORWARD "5
+"##;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100)
+        .unwrap()
+        .with_coordinates(logo_runner::Coordinates::Logo);
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!((runner.get_pos_x(), runner.get_pos_y()), (35, 20));
+    assert_eq!(runner.bounds(), [-50, -50, 50, 50]);
+    let svg = save_svg(runner);
+    assert_eq!(
+        strokes(&svg),
+        [
+            "M 70 50 L 70 40",
+            "M 70 40 L 80 40",
+            "M 80 40 L 80 30",
+            "M 80 30 L 85 30"
+        ]
+    );
+
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(
+        "SETSCALE \"0.5 ROTATE \"180 MAKE \"b BOUNDS POPTRANSFORM\n".to_string(),
+    );
+    let err = interpreter.interpret(&mut runner).unwrap_err();
+    assert!(err.contains("transform stack is empty"), "{}", err);
+    assert_eq!(runner.bounds(), [-200, -200, 0, 0]);
+    let mut interpreter = logo_interpreter::LogoInterpreter::default("SETSCALE \"0\n".to_string());
+    assert!(interpreter.interpret(&mut runner).is_err());
+
+    // short moves at a fractional scale still add up
+    let content = format!("PENDOWN SETSCALE \"0.4\n{}", "FORWARD \"1\n".repeat(10));
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content);
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    assert_eq!(runner.get_pos_y(), 115);
+    let svg = save_svg(runner);
+    assert!(svg.contains(r#"d="M 50 47 L 50 46""#));
+}
+
+#[test]
+fn test_fit_and_crop() {
+    let content = r##"
+SETPENSIZE "2
+SETPOS [10 10]
+PENDOWN
+TURN "90
+FORWARD "20
+TURN "90
+FORWARD "10
+"##;
+    let run = |framing| {
+        let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+        let mut runner = logo_runner::LogoRunner::new(100, 50)
+            .unwrap()
+            .with_framing(framing);
+        interpreter.interpret(&mut runner).unwrap();
+        runner
+    };
+    let bounds = run(logo_runner::Framing::Canvas).drawing_bounds().unwrap();
+    assert_eq!(
+        (bounds.left(), bounds.top(), bounds.right(), bounds.bottom()),
+        (10.0, 9.0, 31.0, 20.0)
+    );
+
+    let svg = save_svg(run(logo_runner::Framing::Crop(4)));
+    assert!(svg.starts_with(r#"<svg width="29" height="19" viewBox="6 5 29 19""#));
+    let svg = save_svg(run(logo_runner::Framing::Fit(5)));
+    assert!(svg.starts_with(r#"<svg width="100" height="50" viewBox="6.75 7.625 27.5 13.75""#));
+
+    let path = save_as(run(logo_runner::Framing::Crop(0)), "png");
+    let image = tiny_skia::Pixmap::load_png(&path).unwrap();
+    assert_eq!((image.width(), image.height()), (21, 11));
+    assert_eq!(image.pixel(10, 0).unwrap().red(), 255);
+
+    // the line runs off the top of the canvas, the framed png still shows all of it
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(
+        "PENDOWN SETPENSIZE \"4 FORWARD \"100\n".to_string(),
+    );
+    let mut runner = logo_runner::LogoRunner::new(100, 50)
+        .unwrap()
+        .with_framing(logo_runner::Framing::Fit(0));
+    interpreter.interpret(&mut runner).unwrap();
+    runner.save(&path).unwrap();
+    let image = tiny_skia::Pixmap::load_png(&path).unwrap();
+    assert_eq!((image.width(), image.height()), (100, 50));
+    assert_eq!(image.pixel(50, 2).unwrap().red(), 255);
+    assert_eq!(image.pixel(50, 47).unwrap().red(), 255);
+    assert_eq!(image.pixel(53, 25).unwrap().red(), 0);
+}
+
+#[test]
+fn test_display_list() {
+    let content = r##"
+PENDOWN
+FORWARD "10
+TO side
+    FORWARD "5
+END
+NEWTURTLE PENDOWN
+SETPENCOLOR "red SETPENSIZE "3
+side
+PENUP
+IF EQ WHO "1 [ BACK "5 TURN "90 ]
+"##;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    let items = runner.display_list().items();
+    assert_eq!(items.len(), 4);
+    assert_eq!(
+        items[0].op,
+        logo_display_list::DrawOp::Segment {
+            from: (50.0, 50.0),
+            to: (50.0, 40.0),
+            color: logo_color::LogoColor::rgb(255, 255, 255),
+            style: logo_canvas::PenStyle::default(),
+        }
+    );
+    assert_eq!(items[0].turtle, 0);
+    assert_eq!(&content[items[0].span.clone().unwrap()], "FORWARD \"10");
+    let logo_display_list::DrawOp::Segment { color, style, .. } = &items[1].op else {
+        panic!("expected a segment: {:?}", items[1].op);
+    };
+    assert_eq!(
+        (*color, style.width),
+        (logo_color::LogoColor::rgb(255, 0, 0), 3)
+    );
+    assert_eq!(items[1].turtle, 1);
+    assert_eq!(&content[items[1].span.clone().unwrap()], "FORWARD \"5");
+    // moves without drawing are kept too, with where they come from in blocks
+    assert_eq!(
+        items[2].op,
+        logo_display_list::DrawOp::Motion {
+            from: (50.0, 45.0),
+            to: (50.0, 50.0),
+            heading: 0,
+            visible: false,
+        }
+    );
+    assert_eq!(&content[items[2].span.clone().unwrap()], "BACK \"5");
+    assert_eq!(
+        items[3].op,
+        logo_display_list::DrawOp::Motion {
+            from: (50.0, 50.0),
+            to: (50.0, 50.0),
+            heading: 90,
+            visible: false,
+        }
+    );
+    assert_eq!(&content[items[3].span.clone().unwrap()], "TURN \"90");
+
+    struct Counter(usize);
+    impl logo_display_list::DisplayBackend for Counter {
+        fn apply(&mut self, _: &logo_display_list::DisplayItem) -> Result<(), String> {
+            self.0 += 1;
+            Ok(())
+        }
+    }
+    let mut counter = Counter(0);
+    runner.display_list().replay(&mut counter).unwrap();
+    assert_eq!(counter.0, 4);
+}
+
+#[test]
+fn test_json_trace() {
+    let content = r#"
+PENDOWN
+FORWARD "10
+LABEL "say\"hi
+SHOWTURTLE
+"#;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100).unwrap();
+    interpreter.interpret(&mut runner).unwrap();
+    let json = std::fs::read_to_string(save_as(runner, "json")).unwrap();
+    assert!(json.contains("\"version\": 1"));
+    assert!(json.contains("\"bounds\": [40.883, -0.084, 52.496, 50]"));
+    assert!(json.contains(
+        "{\"type\": \"segment\", \"turtle\": 0, \"span\": [9, 20], \"from\": [50, 50], \"to\": [50, 40], \"color\": \"#ffffff\", \"width\": 1, \"pattern\": \"solid\", \"mode\": \"paint\"}"
+    ));
+    assert!(json.contains(r#""text": "say\\\"hi""#));
+    assert!(json.contains(
+        "{\"type\": \"motion\", \"turtle\": 0, \"span\": [36, 46], \"from\": [50, 40], \"to\": [50, 40], \"heading\": 0, \"visible\": true}"
+    ));
+    // the visible turtle is a state, not a drawn shape
+    assert!(!json.contains("\"shape\""));
+    assert!(json.contains(
+        "{\"x\": 50, \"y\": 40, \"heading\": 0, \"pen_down\": true, \"color\": \"#ffffff\", \"pen_size\": 1, \"visible\": true}"
+    ));
+}
+
+#[test]
+fn test_animated_svg() {
+    let content = r#"
+PENDOWN
+FORWARD "20
+SETPENPATTERN "dashed
+RIGHT "10
+PENUP
+FORWARD "10
+TURN "90
+NEWTURTLE
+PENDOWN
+FORWARD "10
+"#;
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content.to_string());
+    let mut runner = logo_runner::LogoRunner::new(100, 100)
+        .unwrap()
+        .with_animation(logo_canvas::Animation {
+            speed: 10.0,
+            turtle: true,
+        });
+    interpreter.interpret(&mut runner).unwrap();
+    let svg = save_svg(runner);
+    // solid lines are drawn in over their length at the given speed
+    assert!(svg.contains("<g visibility=\"hidden\"><set attributeName=\"visibility\" to=\"visible\" begin=\"0s\" fill=\"freeze\"/><path fill=\"none\" stroke=\"#ffffff\" d=\"M 50 50 L 50 30\" pathLength=\"1\" stroke-dasharray=\"1\" stroke-dashoffset=\"1\"><animate attributeName=\"stroke-dashoffset\" to=\"0\" begin=\"0s\" dur=\"2s\" fill=\"freeze\"/></path></g>"));
+    // patterned lines show up when their turn comes
+    assert!(svg.contains("<set attributeName=\"visibility\" to=\"visible\" begin=\"2s\" fill=\"freeze\"/><path fill=\"none\" stroke=\"#ffffff\" stroke-dasharray=\"4 3\" d=\"M 50 30 L 60 30\"/>"));
+    assert!(svg.contains("<animateMotion path=\"M 50 30 L 60 30\" rotate=\"auto\" begin=\"2s\" dur=\"1s\" fill=\"freeze\"/>"));
+    // the turtle also goes where it moves without drawing and turns in place
+    assert!(svg.contains("<animateMotion path=\"M 60 30 L 60 20\" rotate=\"-90\" begin=\"3s\" dur=\"1s\" fill=\"freeze\"/>"));
+    assert!(svg.contains("<animateMotion path=\"M 60 20\" rotate=\"0\" begin=\"4s\"/>"));
+    // every turtle has a glyph of its own
+    assert_eq!(svg.matches("d=\"M 10 0 L -5 5 L -5 -5 Z\"").count(), 2);
+    assert!(svg.contains("<animateMotion path=\"M 50 50 L 50 40\" rotate=\"auto\" begin=\"4s\" dur=\"1s\" fill=\"freeze\"/>"));
+}
+
+#[test]
+fn test_animation_needs_svg() {
+    let animation = logo_canvas::Animation {
+        speed: 10.0,
+        turtle: false,
+    };
+    for extension in ["png", "json"] {
+        let runner = logo_runner::LogoRunner::new(100, 100)
+            .unwrap()
+            .with_animation(animation);
+        let path = std::env::temp_dir().join(format!("rslogo_animated.{}", extension));
+        assert_eq!(
+            runner.save(&path),
+            Err("only svg images can be animated".to_string())
+        );
+    }
+}
@@ -0,0 +1,242 @@
+use tiny_skia::Rect;
+
+use crate::logo_canvas::{svg_number, PenMode, PenPattern, PenStyle};
+use crate::logo_color::LogoColor;
+use crate::logo_display_list::{DisplayItem, DrawOp};
+
+/// Bumped whenever a field changes meaning or goes away, adding fields keeps it.
+pub const JSON_VERSION: u32 = 1;
+
+/// Where a turtle ended up, in canvas pixels.
+pub struct TurtleState {
+    pub x: i32,
+    pub y: i32,
+    pub heading: i32,
+    pub pen_down: bool,
+    pub color: LogoColor,
+    pub pen_size: u32,
+    pub visible: bool,
+}
+
+/// The drawing trace as a JSON document. Positions are canvas pixels with y pointing
+/// down, colors are `#rrggbb` or `#rrggbbaa` strings:
+///
+/// - `version`: [`JSON_VERSION`]
+/// - `width`, `height`, `background`: the canvas
+/// - `bounds`: `[left, top, right, bottom]` of everything drawn, or `null`
+/// - `operations`: in drawing order, each with a `type`, the `turtle` that drew it and
+///   the `span` of the statement it came from as `[start, end]` bytes or `null`
+///   - `segment`: `from`, `to`
+///   - `arc`: `from`, `to`, `center`, `radius`, `sweep` in degrees clockwise
+///   - `shape`: `points` of a closed outline
+///   - strokes above also have `color`, `width`, `pattern` and `mode`
+///   - `label`: `at`, `heading`, `text`, `color`, `height`, `font`, `mode`
+///   - `begin_fill`, then `fill`: `points`, `color`, painted below what was drawn since
+///   - `flood_fill`: `at`, `color`
+///   - `clear`, `background`: `color`
+///   - `motion`: `from`, `to`, `heading`, `visible`, the turtle moving without drawing,
+///     turning, showing or hiding
+/// - `turtles`: `x`, `y`, `heading`, `pen_down`, `color`, `pen_size`, `visible`
+pub fn trace_json(
+    size: (u32, u32),
+    background: LogoColor,
+    bounds: Option<Rect>,
+    items: &[DisplayItem],
+    turtles: &[TurtleState],
+) -> String {
+    let bounds = match bounds {
+        Some(bounds) => json_list(&[bounds.left(), bounds.top(), bounds.right(), bounds.bottom()]),
+        None => "null".to_string(),
+    };
+    let operations = items.iter().map(json_item).collect::<Vec<_>>();
+    let turtles = turtles.iter().map(json_turtle).collect::<Vec<_>>();
+    format!(
+        "{{\n  \"version\": {},\n  \"width\": {},\n  \"height\": {},\n  \"background\": {},\n  \"bounds\": {},\n  \"operations\": [{}],\n  \"turtles\": [{}]\n}}\n",
+        JSON_VERSION,
+        size.0,
+        size.1,
+        json_color(background),
+        bounds,
+        json_lines(&operations),
+        json_lines(&turtles)
+    )
+}
+
+/// One element per line, so traces diff well.
+fn json_lines(elements: &[String]) -> String {
+    if elements.is_empty() {
+        return String::new();
+    }
+    format!("\n    {}\n  ", elements.join(",\n    "))
+}
+
+fn json_item(item: &DisplayItem) -> String {
+    let span = match &item.span {
+        Some(span) => format!("[{}, {}]", span.start, span.end),
+        None => "null".to_string(),
+    };
+    let mut fields = vec![
+        String::new(),
+        format!("\"turtle\": {}", item.turtle),
+        format!("\"span\": {}", span),
+    ];
+    let kind = match &item.op {
+        DrawOp::Segment {
+            from,
+            to,
+            color,
+            style,
+        } => {
+            fields.push(format!("\"from\": {}", json_list(&[from.0, from.1])));
+            fields.push(format!("\"to\": {}", json_list(&[to.0, to.1])));
+            fields.extend(json_pen(*color, style));
+            "segment"
+        }
+        DrawOp::Arc {
+            x,
+            y,
+            arc,
+            color,
+            style,
+        } => {
+            let (end_x, end_y) = arc.end();
+            let (center_x, center_y) = arc.center();
+            fields.push(format!("\"from\": [{}, {}]", x, y));
+            fields.push(format!("\"to\": [{}, {}]", end_x, end_y));
+            fields.push(format!("\"center\": {}", json_list(&[center_x, center_y])));
+            fields.push(format!("\"radius\": {}", svg_number(arc.radius())));
+            fields.push(format!("\"sweep\": {}", svg_number(arc.sweep())));
+            fields.extend(json_pen(*color, style));
+            "arc"
+        }
+        DrawOp::Shape {
+            points,
+            color,
+            style,
+        } => {
+            let points = points
+                .iter()
+                .map(|(x, y)| json_list(&[*x, *y]))
+                .collect::<Vec<_>>();
+            fields.push(format!("\"points\": [{}]", points.join(", ")));
+            fields.extend(json_pen(*color, style));
+            "shape"
+        }
+        DrawOp::Label {
+            x,
+            y,
+            direction,
+            text,
+            color,
+            mode,
+            style,
+        } => {
+            fields.push(format!("\"at\": [{}, {}]", x, y));
+            fields.push(format!("\"heading\": {}", direction));
+            fields.push(format!("\"text\": {}", json_string(text)));
+            fields.push(format!("\"color\": {}", json_color(*color)));
+            fields.push(format!("\"height\": {}", style.height));
+            fields.push(format!("\"font\": {}", json_string(&style.font)));
+            fields.push(format!("\"mode\": {}", json_string(json_mode(*mode))));
+            "label"
+        }
+        DrawOp::BeginFill => "begin_fill",
+        DrawOp::EndFill { points, color } => {
+            let points = points
+                .iter()
+                .map(|(x, y)| format!("[{}, {}]", x, y))
+                .collect::<Vec<_>>();
+            fields.push(format!("\"points\": [{}]", points.join(", ")));
+            fields.push(format!("\"color\": {}", json_color(*color)));
+            "fill"
+        }
+        DrawOp::FloodFill { x, y, color } => {
+            fields.push(format!("\"at\": [{}, {}]", x, y));
+            fields.push(format!("\"color\": {}", json_color(*color)));
+            "flood_fill"
+        }
+        DrawOp::Clear => "clear",
+        DrawOp::Background(color) => {
+            fields.push(format!("\"color\": {}", json_color(*color)));
+            "background"
+        }
+        DrawOp::Motion {
+            from,
+            to,
+            heading,
+            visible,
+        } => {
+            fields.push(format!("\"from\": {}", json_list(&[from.0, from.1])));
+            fields.push(format!("\"to\": {}", json_list(&[to.0, to.1])));
+            fields.push(format!("\"heading\": {}", heading));
+            fields.push(format!("\"visible\": {}", visible));
+            "motion"
+        }
+    };
+    fields[0] = format!("\"type\": {}", json_string(kind));
+    format!("{{{}}}", fields.join(", "))
+}
+
+fn json_pen(color: LogoColor, style: &PenStyle) -> [String; 4] {
+    let pattern = match style.pattern {
+        PenPattern::Solid => "solid",
+        PenPattern::Dashed => "dashed",
+        PenPattern::Dotted => "dotted",
+        PenPattern::DashDot => "dashdot",
+    };
+    [
+        format!("\"color\": {}", json_color(color)),
+        format!("\"width\": {}", style.width),
+        format!("\"pattern\": {}", json_string(pattern)),
+        format!("\"mode\": {}", json_string(json_mode(style.mode))),
+    ]
+}
+
+fn json_mode(mode: PenMode) -> &'static str {
+    match mode {
+        PenMode::Paint => "paint",
+        PenMode::Erase => "erase",
+        PenMode::Reverse => "reverse",
+    }
+}
+
+fn json_turtle(turtle: &TurtleState) -> String {
+    format!(
+        "{{\"x\": {}, \"y\": {}, \"heading\": {}, \"pen_down\": {}, \"color\": {}, \"pen_size\": {}, \"visible\": {}}}",
+        turtle.x,
+        turtle.y,
+        turtle.heading,
+        turtle.pen_down,
+        json_color(turtle.color),
+        turtle.pen_size,
+        turtle.visible
+    )
+}
+
+fn json_list(numbers: &[f32]) -> String {
+    let numbers = numbers.iter().map(|n| svg_number(*n)).collect::<Vec<_>>();
+    format!("[{}]", numbers.join(", "))
+}
+
+fn json_color(color: LogoColor) -> String {
+    if color.alpha == 255 {
+        json_string(&color.to_hex())
+    } else {
+        json_string(&format!("{}{:02x}", color.to_hex(), color.alpha))
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut escaped = String::from("\"");
+    for ch in text.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            ch if (ch as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
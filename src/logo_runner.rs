@@ -1,109 +1,966 @@
+use std::ops::Range;
 use std::path::PathBuf;
 
-use unsvg::Image;
+use tiny_skia::{LineCap, LineJoin, Rect, Transform as PixelTransform};
+
+use crate::logo_canvas::{
+    Animation, LabelStyle, LogoCanvas, PenMode, PenPattern, PenStyle, TurtleArc,
+};
+use crate::logo_color::LogoColor;
+use crate::logo_display_list::{DisplayBackend, DisplayItem, DisplayList, DrawOp};
+use crate::logo_json::{trace_json, TurtleState};
+
+const PALETTE_SIZE: usize = 256;
+
+// the triangle drawn for a turtle, x to its right and y straight ahead
+const TURTLE_SHAPE: [(f32, f32); 3] = [(0.0, 10.0), (5.0, -5.0), (-5.0, -5.0)];
 
 pub struct LogoRunner {
+    // turtles are numbered by their index, commands go to the current one
+    turtles: Vec<Turtle>,
+    current: usize,
+    // states saved by PUSHTURTLE, one stack for each turtle
+    saved: Vec<Vec<Turtle>>,
+    palette: Vec<LogoColor>,
+    // the center of the canvas
+    home: (i32, i32),
+    coordinates: Coordinates,
+    transform: Transform,
+    // transforms saved by PUSHTRANSFORM
+    transforms: Vec<Transform>,
+    boundary: Boundary,
+    framing: Framing,
+    // svg output draws itself when set
+    animation: Option<Animation>,
+    size: (i32, i32),
+    output: LogoCanvas,
+    display_list: DisplayList,
+    // the statement being run, for the display list
+    source_span: Option<Range<usize>>,
+}
+
+/// The state each turtle keeps for itself, the canvas is shared.
+#[derive(Debug, Clone)]
+struct Turtle {
     pen_status: PenStatus,
-    color: unsvg::Color,
-    color_index: usize,
+    pen_style: PenStyle,
+    label_style: LabelStyle,
+    color: LogoColor,
+    // set when the color was picked from the palette
+    color_index: Option<usize>,
+    fill_color: LogoColor,
+    // turtle positions since BEGINFILL
+    fill_points: Option<Vec<(i32, i32)>>,
     x: i32,
     y: i32,
+    // the turtle moves whole pixels, what is left of a scaled move is added to the next one
+    carry: f32,
     direction: i32,
-    output: Image,
+    visible: bool,
+    shape: Vec<(f32, f32)>,
+}
+
+impl Turtle {
+    fn new(x: i32, y: i32) -> Self {
+        Turtle {
+            pen_status: PenStatus::Up,
+            pen_style: PenStyle::default(),
+            label_style: LabelStyle::default(),
+            color: LogoColor::rgb(255, 255, 255),
+            color_index: Some(7), // white color index is 7
+            fill_color: LogoColor::rgb(255, 255, 255),
+            fill_points: None,
+            x,
+            y,
+            carry: 0.0,
+            direction: 0,
+            visible: false,
+            shape: TURTLE_SHAPE.to_vec(),
+        }
+    }
+
+    fn position(&self) -> (f32, f32) {
+        (self.x as f32, self.y as f32)
+    }
+
+    fn move_to(&mut self, x: i32, y: i32) {
+        self.x = x;
+        self.y = y;
+        if let Some(points) = self.fill_points.as_mut() {
+            points.push((x, y));
+        }
+    }
+
+    /// The shape turned with the turtle and placed at its position, drawn through the transform.
+    fn outline(&self, transform: &Transform) -> Vec<(f32, f32)> {
+        let (sin, cos) = (self.direction as f32).to_radians().sin_cos();
+        let (x, y) = (self.x as f32, self.y as f32);
+        self.shape
+            .iter()
+            .map(|(dx, dy)| {
+                let (dx, dy) = transform.apply((dx * cos + dy * sin, dx * sin - dy * cos));
+                (x + dx, y + dy)
+            })
+            .collect()
+    }
+}
+
+/// How program units map onto the canvas, shared by all turtles.
+/// Vectors are scaled, mirrored left to right and then rotated clockwise,
+/// with y pointing down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Transform {
+    scale: f32,
+    mirrored: bool,
+    rotation: i32,
+    // where the program origin has been moved to, in pixels
+    offset: (f32, f32),
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            scale: 1.0,
+            mirrored: false,
+            rotation: 0,
+            offset: (0.0, 0.0),
+        }
+    }
+}
+
+impl Transform {
+    fn apply(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let x = if self.mirrored { -x } else { x } * self.scale;
+        let y = y * self.scale;
+        let (sin, cos) = (self.rotation as f32).to_radians().sin_cos();
+        (x * cos - y * sin, x * sin + y * cos)
+    }
+
+    fn invert(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let (sin, cos) = (self.rotation as f32).to_radians().sin_cos();
+        let (x, y) = (x * cos + y * sin, y * cos - x * sin);
+        let x = if self.mirrored { -x } else { x } / self.scale;
+        (x, y / self.scale)
+    }
+
+    /// The direction and length on the canvas of a move in program units.
+    fn motion(&self, direction: i32, distance: i32) -> (i32, f32) {
+        let direction = if self.mirrored { -direction } else { direction };
+        (
+            (direction + self.rotation) % 360,
+            distance as f32 * self.scale,
+        )
+    }
+}
+
+/// What happens when the turtle reaches the edge of the canvas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Boundary {
+    /// keep going off the canvas
+    Window,
+    /// come back in on the opposite edge
+    Wrap,
+    /// refuse to move off the canvas
+    Fence,
+}
+
+/// Which part of the drawing is saved, and at what size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Framing {
+    /// the canvas as it is
+    #[default]
+    Canvas,
+    /// scale the drawing to fill the canvas, leaving a margin in pixels around it
+    Fit(u32),
+    /// shrink the canvas to the drawing and a margin in pixels around it
+    Crop(u32),
+}
+
+/// How positions are read and set by programs, the turtle always moves in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Coordinates {
+    /// origin at the top left corner, y pointing down
+    #[default]
+    Pixel,
+    /// origin at the center of the canvas, y pointing up
+    Logo,
 }
 
 impl LogoRunner {
-    pub fn new(size_x: u32, size_y: u32) -> Self {
+    pub fn new(size_x: u32, size_y: u32) -> Result<Self, String> {
         let pos_x = (size_x / 2) as i32;
         let pos_y = (size_y / 2) as i32;
-        LogoRunner {
-            pen_status: PenStatus::Up,
-            color: unsvg::Color::white(), // TODO
-            color_index: 7,  // white color index is 7
-            x: pos_x,
-            y: pos_y,
-            direction: 0,
-            output: Image::new(size_x, size_y),
+        Ok(LogoRunner {
+            turtles: vec![Turtle::new(pos_x, pos_y)],
+            current: 0,
+            saved: vec![Vec::new()],
+            palette: unsvg::COLORS.iter().map(|c| (*c).into()).collect(),
+            home: (pos_x, pos_y),
+            coordinates: Coordinates::default(),
+            transform: Transform::default(),
+            transforms: Vec::new(),
+            boundary: Boundary::Window,
+            framing: Framing::default(),
+            animation: None,
+            size: (size_x as i32, size_y as i32),
+            output: LogoCanvas::new(size_x, size_y)?,
+            display_list: DisplayList::default(),
+            source_span: None,
+        })
+    }
+
+    pub fn with_coordinates(mut self, coordinates: Coordinates) -> Self {
+        self.coordinates = coordinates;
+        self
+    }
+
+    pub fn with_framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    pub fn with_animation(mut self, animation: Animation) -> Self {
+        self.animation = Some(animation);
+        self
+    }
+
+    pub fn display_list(&self) -> &DisplayList {
+        &self.display_list
+    }
+
+    /// Attribute what is drawn next to a part of the program source.
+    pub fn set_source_span(&mut self, span: Option<Range<usize>>) {
+        self.source_span = span;
+    }
+
+    /// Draw on the canvas and keep the operation in the display list.
+    fn draw_op(&mut self, op: DrawOp) -> Result<(), String> {
+        let item = DisplayItem {
+            op,
+            turtle: self.current,
+            span: self.source_span.clone(),
+        };
+        self.output.apply(&item)?;
+        self.display_list.push(item);
+        Ok(())
+    }
+
+    /// Keep a change of the turtle that draws nothing in the display list, so a replay can
+    /// follow it. A move is a straight line, even along an arc.
+    fn motion(&mut self, from: (f32, f32), to: (f32, f32)) {
+        let turtle = self.turtle();
+        let op = DrawOp::Motion {
+            from,
+            to,
+            heading: self.transform.motion(turtle.direction, 0).0,
+            visible: turtle.visible,
+        };
+        self.display_list.push(DisplayItem {
+            op,
+            turtle: self.current,
+            span: self.source_span.clone(),
+        });
+    }
+
+    /// The area covered by everything drawn in pixels, on the canvas or not.
+    pub fn drawing_bounds(&self) -> Option<Rect> {
+        self.output.bounds()
+    }
+
+    fn turtle(&self) -> &Turtle {
+        &self.turtles[self.current]
+    }
+
+    fn turtle_mut(&mut self) -> &mut Turtle {
+        &mut self.turtles[self.current]
+    }
+
+    /// Add a turtle at the center and make it the current one, returning its number.
+    pub fn new_turtle(&mut self) -> usize {
+        self.turtles.push(Turtle::new(self.home.0, self.home.1));
+        self.saved.push(Vec::new());
+        self.current = self.turtles.len() - 1;
+        self.current
+    }
+
+    /// Send the following commands to another turtle.
+    pub fn tell(&mut self, turtle: usize) -> Result<(), String> {
+        if turtle >= self.turtles.len() {
+            return Err(format!("no such turtle: {}", turtle));
         }
+        self.current = turtle;
+        Ok(())
+    }
+
+    pub fn who(&self) -> usize {
+        self.current
+    }
+
+    /// Draw the turtle in the saved image, at wherever it ends up.
+    pub fn show_turtle(&mut self) {
+        self.turtle_mut().visible = true;
+        let at = self.turtle().position();
+        self.motion(at, at);
+    }
+
+    pub fn hide_turtle(&mut self) {
+        self.turtle_mut().visible = false;
+        let at = self.turtle().position();
+        self.motion(at, at);
+    }
+
+    /// Use a polygon of points relative to the turtle as its shape,
+    /// an empty one goes back to the triangle.
+    pub fn set_shape(&mut self, shape: Vec<(f32, f32)>) -> Result<(), String> {
+        self.turtle_mut().shape = match shape.len() {
+            0 => TURTLE_SHAPE.to_vec(),
+            1 | 2 => return Err("a shape needs at least 3 points".to_string()),
+            _ => shape,
+        };
+        Ok(())
+    }
+
+    /// Leave a copy of the turtle's shape on the canvas, whether the pen is down or not.
+    pub fn stamp(&mut self) -> Result<(), String> {
+        let turtle = self.turtle();
+        self.draw_op(DrawOp::Shape {
+            points: turtle.outline(&self.transform),
+            color: turtle.color,
+            style: PenStyle::default(),
+        })
+    }
+
+    /// Remember where the turtle is and how its pen is set up.
+    pub fn push_turtle(&mut self) {
+        let state = self.turtle().clone();
+        self.saved[self.current].push(state);
+    }
+
+    /// Go back to the last pushed state without drawing, a fill in progress carries on.
+    pub fn pop_turtle(&mut self) -> Result<(), String> {
+        let mut state = self.saved[self.current]
+            .pop()
+            .ok_or("turtle stack is empty")?;
+        state.fill_points = self.turtle_mut().fill_points.take();
+        let from = self.turtle().position();
+        let (x, y) = (state.x, state.y);
+        *self.turtle_mut() = state;
+        self.turtle_mut().move_to(x, y);
+        self.motion(from, self.turtle().position());
+        Ok(())
     }
 
     pub fn pen_up(&mut self) {
-        self.pen_status = PenStatus::Up;
+        self.turtle_mut().pen_status = PenStatus::Up;
     }
 
     pub fn pen_down(&mut self) {
-        self.pen_status = PenStatus::Down;
+        self.turtle_mut().pen_status = PenStatus::Down;
     }
 
-    pub fn set_pos(&mut self, x: i32, y: i32) {
-        self.x = x;
-        self.y = y;
+    /// Move to a position given in the selected coordinates, without drawing.
+    pub fn set_pos(&mut self, x: i32, y: i32) -> Result<(), String> {
+        let (x, y) = self.program_to_pixel(x, y);
+        let (x, y) = self.bound(x, y)?;
+        let from = self.turtle().position();
+        self.turtle_mut().move_to(x, y);
+        self.motion(from, self.turtle().position());
+        Ok(())
+    }
+
+    /// Where the program origin is on the canvas before any TRANSLATE.
+    fn origin(&self) -> (f32, f32) {
+        match self.coordinates {
+            Coordinates::Pixel => (0.0, 0.0),
+            Coordinates::Logo => (self.home.0 as f32, self.home.1 as f32),
+        }
+    }
+
+    /// Flip the y axis of a vector in the selected coordinates to point down, or back.
+    fn flip(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        match self.coordinates {
+            Coordinates::Pixel => (x, y),
+            Coordinates::Logo => (x, -y),
+        }
+    }
+
+    fn program_to_pixel(&self, x: i32, y: i32) -> (i32, i32) {
+        let (dx, dy) = self.transform.apply(self.flip((x as f32, y as f32)));
+        let (origin, offset) = (self.origin(), self.transform.offset);
+        (
+            (origin.0 + offset.0 + dx).round() as i32,
+            (origin.1 + offset.1 + dy).round() as i32,
+        )
+    }
+
+    fn pixel_to_program(&self, x: i32, y: i32) -> (i32, i32) {
+        let (origin, offset) = (self.origin(), self.transform.offset);
+        let vector = (
+            x as f32 - origin.0 - offset.0,
+            y as f32 - origin.1 - offset.1,
+        );
+        let (x, y) = self.flip(self.transform.invert(vector));
+        (x.round() as i32, y.round() as i32)
+    }
+
+    /// Scale the drawing from the current origin, 1 is the original size.
+    pub fn set_scale(&mut self, scale: f32) -> Result<(), String> {
+        if !(scale > 0.0 && scale.is_finite()) {
+            return Err(format!("invalid scale: {}", scale));
+        }
+        self.transform.scale = scale;
+        Ok(())
+    }
+
+    /// Move the origin, in the current units and orientation.
+    pub fn translate(&mut self, x: i32, y: i32) {
+        let (dx, dy) = self.transform.apply(self.flip((x as f32, y as f32)));
+        self.transform.offset.0 += dx;
+        self.transform.offset.1 += dy;
+    }
+
+    /// Rotate the drawing clockwise around the current origin.
+    pub fn rotate(&mut self, degree: i32) {
+        self.transform.rotation = (self.transform.rotation + degree) % 360;
+    }
+
+    /// Flip the drawing left to right, turns go the other way.
+    pub fn mirror(&mut self) {
+        self.transform.mirrored = !self.transform.mirrored;
+    }
+
+    pub fn push_transform(&mut self) {
+        self.transforms.push(self.transform);
+    }
+
+    pub fn pop_transform(&mut self) -> Result<(), String> {
+        self.transform = self.transforms.pop().ok_or("transform stack is empty")?;
+        Ok(())
+    }
+
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        self.boundary = boundary;
+        if boundary == Boundary::Wrap {
+            // turtles left outside in window mode are brought back in
+            for index in 0..self.turtles.len() {
+                let (x, y) = self.wrap(self.turtles[index].x, self.turtles[index].y);
+                self.turtles[index].move_to(x, y);
+            }
+        }
+    }
+
+    fn in_bounds(&self, x: i32, y: i32) -> bool {
+        (0..=self.size.0).contains(&x) && (0..=self.size.1).contains(&y)
+    }
+
+    fn wrap(&self, x: i32, y: i32) -> (i32, i32) {
+        if self.in_bounds(x, y) {
+            return (x, y);
+        }
+        (x.rem_euclid(self.size.0), y.rem_euclid(self.size.1))
+    }
+
+    /// Where a jump to a position ends up under the boundary mode.
+    fn bound(&self, x: i32, y: i32) -> Result<(i32, i32), String> {
+        match self.boundary {
+            Boundary::Window => Ok((x, y)),
+            Boundary::Wrap => Ok(self.wrap(x, y)),
+            Boundary::Fence if self.in_bounds(x, y) => Ok((x, y)),
+            Boundary::Fence => Err(format!("turtle out of bounds: {} {}", x, y)),
+        }
+    }
+
+    /// Move back to the center facing up, without drawing.
+    pub fn home(&mut self) {
+        let (x, y) = self.home;
+        let from = self.turtle().position();
+        self.turtle_mut().move_to(x, y);
+        self.turtle_mut().direction = 0;
+        self.motion(from, self.turtle().position());
+    }
+
+    /// Erase the drawing, the turtle stays where it is.
+    pub fn clean(&mut self) -> Result<(), String> {
+        self.draw_op(DrawOp::Clear)
+    }
+
+    pub fn clear_screen(&mut self) -> Result<(), String> {
+        self.clean()?;
+        self.home();
+        Ok(())
+    }
+
+    pub fn set_background(&mut self, color: LogoColor) -> Result<(), String> {
+        self.draw_op(DrawOp::Background(color))
+    }
+
+    pub fn get_background(&self) -> LogoColor {
+        self.output.get_background()
+    }
+
+    pub fn set_fill_color(&mut self, color: LogoColor) {
+        self.turtle_mut().fill_color = color;
+    }
+
+    /// Start a fill with the current turtle. The canvas holds one fill at a time, so
+    /// another turtle can't begin or end a fill until the open one is ended.
+    pub fn begin_fill(&mut self) -> Result<(), String> {
+        self.check_fill_turtle()?;
+        self.draw_op(DrawOp::BeginFill)?;
+        self.turtle_mut().fill_points = Some(vec![(self.turtle().x, self.turtle().y)]);
+        Ok(())
+    }
+
+    pub fn end_fill(&mut self) -> Result<(), String> {
+        self.check_fill_turtle()?;
+        let points = self.turtle_mut().fill_points.take().unwrap_or_default();
+        let color = self.turtle().fill_color;
+        self.draw_op(DrawOp::EndFill { points, color })
+    }
+
+    fn check_fill_turtle(&self) -> Result<(), String> {
+        match self.turtles.iter().position(|t| t.fill_points.is_some()) {
+            Some(index) if index != self.current => {
+                Err(format!("fill already started by turtle {}", index))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn fill(&mut self) -> Result<(), String> {
+        let turtle = self.turtle();
+        let (x, y, color) = (turtle.x, turtle.y, turtle.fill_color);
+        self.draw_op(DrawOp::FloodFill { x, y, color })
     }
 
     pub fn draw_forward(&mut self, distance: i32) -> Result<(), String> {
-        self.draw(distance, self.direction)
+        self.draw(distance, self.turtle().direction)
     }
 
     pub fn draw_backward(&mut self, distance: i32) -> Result<(), String> {
-        self.draw(distance, (self.direction + 180) % 360)
+        self.draw(distance, (self.turtle().direction + 180) % 360)
     }
 
     pub fn draw_right(&mut self, distance: i32) -> Result<(), String> {
-        self.draw(distance, (self.direction + 90) % 360)
+        self.draw(distance, (self.turtle().direction + 90) % 360)
     }
 
     pub fn draw_left(&mut self, distance: i32) -> Result<(), String> {
-        self.draw(distance, (self.direction + 270) % 360)
+        self.draw(distance, (self.turtle().direction + 270) % 360)
     }
 
     fn draw(&mut self, distance: i32, direction: i32) -> Result<(), String> {
-        let (x, y) = match self.pen_status {
-            PenStatus::Down => self
-                .output
-                .draw_simple_line(self.x, self.y, direction, distance, self.color)?,
-            PenStatus::Up => unsvg::get_end_coordinates(self.x, self.y, direction, distance),
+        let (direction, distance) = self.transform.motion(direction, distance);
+        let distance = distance + self.turtle().carry;
+        self.turtle_mut().carry = distance - distance.round();
+        let distance = distance.round() as i32;
+        let (end_x, end_y) =
+            unsvg::get_end_coordinates(self.turtle().x, self.turtle().y, direction, distance);
+        if !self.in_bounds(end_x, end_y) {
+            match self.boundary {
+                Boundary::Window => {}
+                Boundary::Wrap => return self.draw_wrapped(end_x, end_y),
+                Boundary::Fence => {
+                    return Err(format!("turtle out of bounds: {} {}", end_x, end_y))
+                }
+            }
+        }
+        let turtle = self.turtle();
+        let (from, to) = (turtle.position(), (end_x as f32, end_y as f32));
+        if turtle.pen_status == PenStatus::Down {
+            self.draw_op(DrawOp::Segment {
+                from,
+                to,
+                color: turtle.color,
+                style: turtle.pen_style.clone(),
+            })?;
+        } else {
+            self.motion(from, to);
+        }
+        self.turtle_mut().move_to(end_x, end_y);
+        Ok(())
+    }
+
+    /// Move towards a point off the canvas, the line is split where it crosses
+    /// an edge and continues from the opposite one.
+    fn draw_wrapped(&mut self, end_x: i32, end_y: i32) -> Result<(), String> {
+        let turtle = self.turtle().clone();
+        let (width, height) = (self.size.0 as f32, self.size.1 as f32);
+        let (mut x, mut y) = (turtle.x as f32, turtle.y as f32);
+        let (mut dx, mut dy) = ((end_x - turtle.x) as f32, (end_y - turtle.y) as f32);
+        // fraction of the remaining move before leaving the canvas along one axis
+        let until_edge = |pos: f32, delta: f32, size: f32| {
+            if delta > 0.0 {
+                (size - pos) / delta
+            } else if delta < 0.0 {
+                -pos / delta
+            } else {
+                f32::INFINITY
+            }
         };
-        self.set_pos(x, y);
+        loop {
+            let (tx, ty) = (until_edge(x, dx, width), until_edge(y, dy, height));
+            let t = tx.min(ty).min(1.0);
+            let (next_x, next_y) = (x + dx * t, y + dy * t);
+            if turtle.pen_status == PenStatus::Down && t > 0.0 {
+                self.draw_op(DrawOp::Segment {
+                    from: (x, y),
+                    to: (next_x, next_y),
+                    color: turtle.color,
+                    style: turtle.pen_style.clone(),
+                })?;
+            } else if t > 0.0 {
+                self.motion((x, y), (next_x, next_y));
+            }
+            (x, y) = (next_x, next_y);
+            if t >= 1.0 {
+                break;
+            }
+            dx *= 1.0 - t;
+            dy *= 1.0 - t;
+            if tx <= ty {
+                x = if dx > 0.0 { 0.0 } else { width };
+            }
+            if ty <= tx {
+                y = if dy > 0.0 { 0.0 } else { height };
+            }
+        }
+        self.turtle_mut()
+            .move_to(x.round() as i32, y.round() as i32);
+        Ok(())
+    }
+
+    /// Move along an arc, curving right for positive angles, and turn with it.
+    /// Arcs aren't split at the edges, only their end point is bounded.
+    pub fn draw_arc(&mut self, angle: i32, radius: i32) -> Result<(), String> {
+        if radius < 0 {
+            return Err(format!("invalid radius: {}", radius));
+        }
+        let (direction, scaled_radius) = self.transform.motion(self.turtle().direction, radius);
+        let arc_angle = if self.transform.mirrored {
+            -angle
+        } else {
+            angle
+        };
+        let arc = TurtleArc::new(
+            self.turtle().x,
+            self.turtle().y,
+            direction,
+            arc_angle,
+            scaled_radius.round() as i32,
+        );
+        if self.boundary == Boundary::Fence
+            && arc
+                .points(10.0)
+                .iter()
+                .any(|(x, y)| !self.in_bounds(*x, *y))
+        {
+            return Err(format!("turtle out of bounds: arc of radius {}", radius));
+        }
+        if let Some(points) = self.turtle_mut().fill_points.as_mut() {
+            // the end point is added when moving there
+            let mut arc_points = arc.points(10.0);
+            arc_points.pop();
+            points.extend(arc_points);
+        }
+        let turtle = self.turtle();
+        let (from, pen_down) = (turtle.position(), turtle.pen_status == PenStatus::Down);
+        if pen_down {
+            self.draw_op(DrawOp::Arc {
+                x: turtle.x,
+                y: turtle.y,
+                arc,
+                color: turtle.color,
+                style: turtle.pen_style.clone(),
+            })?;
+        }
+        let (x, y) = arc.end();
+        let (x, y) = self.bound(x, y)?;
+        self.turtle_mut().move_to(x, y);
+        if !pen_down {
+            self.motion(from, self.turtle().position());
+        }
+        self.turn_degree(angle);
         Ok(())
     }
 
     pub fn get_pos_x(&self) -> i32 {
-        self.x
+        self.pixel_to_program(self.turtle().x, self.turtle().y).0
     }
 
     pub fn get_pos_y(&self) -> i32 {
-        self.y
+        self.pixel_to_program(self.turtle().x, self.turtle().y).1
+    }
+
+    /// From the turtle to a position, in program units with y pointing down.
+    fn vector_to(&self, x: i32, y: i32) -> (f32, f32) {
+        let (x, y) = self.program_to_pixel(x, y);
+        self.transform
+            .invert(((x - self.turtle().x) as f32, (y - self.turtle().y) as f32))
+    }
+
+    /// The heading that would point the turtle at a position.
+    pub fn towards(&self, x: i32, y: i32) -> i32 {
+        let (dx, dy) = self.vector_to(x, y);
+        (dx.atan2(-dy).to_degrees().round() as i32).rem_euclid(360)
     }
 
-    pub fn get_color_index(&self) -> i32 {
-        self.color_index as i32
+    pub fn distance(&self, x: i32, y: i32) -> i32 {
+        let (dx, dy) = self.vector_to(x, y);
+        dx.hypot(dy).round() as i32
+    }
+
+    pub fn get_width(&self) -> i32 {
+        self.size.0
+    }
+
+    pub fn get_height(&self) -> i32 {
+        self.size.1
+    }
+
+    /// The area the canvas covers as `[xmin ymin xmax ymax]`.
+    pub fn bounds(&self) -> [i32; 4] {
+        let (width, height) = self.size;
+        let corners = [(0, 0), (width, 0), (0, height), (width, height)]
+            .map(|(x, y)| self.pixel_to_program(x, y));
+        let xs = corners.map(|(x, _)| x);
+        let ys = corners.map(|(_, y)| y);
+        [
+            *xs.iter().min().unwrap_or(&0),
+            *ys.iter().min().unwrap_or(&0),
+            *xs.iter().max().unwrap_or(&0),
+            *ys.iter().max().unwrap_or(&0),
+        ]
+    }
+
+    pub fn is_pen_down(&self) -> bool {
+        self.turtle().pen_status == PenStatus::Down
+    }
+
+    pub fn get_pen_size(&self) -> u32 {
+        self.turtle().pen_style.width
+    }
+
+    pub fn is_turtle_shown(&self) -> bool {
+        self.turtle().visible
+    }
+
+    pub fn get_color_index(&self) -> Option<usize> {
+        self.turtle().color_index
+    }
+
+    pub fn get_color(&self) -> LogoColor {
+        self.turtle().color
     }
 
     pub fn get_direction(&self) -> i32 {
-        self.direction
+        self.turtle().direction
+    }
+
+    pub fn set_color(&mut self, color: usize) -> Result<(), String> {
+        self.turtle_mut().color = *self.palette.get(color).ok_or("invalid color")?;
+        self.turtle_mut().color_index = Some(color);
+        Ok(())
+    }
+
+    pub fn set_color_rgb(&mut self, color: LogoColor) {
+        // an exact palette color still reports its index
+        self.turtle_mut().color_index = self.palette.iter().position(|c| *c == color);
+        self.turtle_mut().color = color;
+    }
+
+    pub fn get_palette(&self, index: usize) -> Option<LogoColor> {
+        self.palette.get(index).copied()
+    }
+
+    /// Remap a palette index, new indices past the end are added with black in between.
+    /// Pens using the index pick up the new color for the lines drawn after it.
+    pub fn set_palette(&mut self, index: usize, color: LogoColor) -> Result<(), String> {
+        if index >= PALETTE_SIZE {
+            return Err(format!("invalid palette index: {}", index));
+        }
+        if index >= self.palette.len() {
+            self.palette.resize(index + 1, LogoColor::rgb(0, 0, 0));
+        }
+        self.palette[index] = color;
+        for turtle in &mut self.turtles {
+            if turtle.color_index == Some(index) {
+                turtle.color = color;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_pen_size(&mut self, size: u32) {
+        self.turtle_mut().pen_style.width = size;
+    }
+
+    /// Switch how the pen draws, putting it down like UCBLogo does.
+    pub fn set_pen_mode(&mut self, mode: PenMode) {
+        self.turtle_mut().pen_style.mode = mode;
+        self.pen_down();
+    }
+
+    pub fn set_pen_pattern(&mut self, pattern: PenPattern) {
+        self.turtle_mut().pen_style.pattern = pattern;
+    }
+
+    pub fn set_line_cap(&mut self, cap: LineCap) {
+        self.turtle_mut().pen_style.cap = cap;
+    }
+
+    pub fn set_line_join(&mut self, join: LineJoin) {
+        self.turtle_mut().pen_style.join = join;
+    }
+
+    /// Draw text at the turtle, whether the pen is down or not.
+    pub fn label(&mut self, text: &str) -> Result<(), String> {
+        let turtle = self.turtle();
+        let (direction, _) = self.transform.motion(turtle.direction, 0);
+        self.draw_op(DrawOp::Label {
+            x: turtle.x,
+            y: turtle.y,
+            direction,
+            text: text.to_string(),
+            color: turtle.color,
+            mode: turtle.pen_style.mode,
+            style: turtle.label_style.clone(),
+        })
+    }
+
+    pub fn set_label_height(&mut self, height: u32) {
+        self.turtle_mut().label_style.height = height;
     }
 
-    pub fn set_color(&mut self, color: usize) {
-        self.color_index = color;
-        self.color = unsvg::COLORS[self.color_index];
+    pub fn set_label_font(&mut self, font: String) {
+        self.turtle_mut().label_style.font = font;
     }
 
     pub fn turn_degree(&mut self, degree: i32) {
-        self.direction = (self.direction + degree) % 360;
+        self.turtle_mut().direction = (self.turtle().direction + degree) % 360;
+        let at = self.turtle().position();
+        self.motion(at, at);
     }
 
-    pub fn save(self, path: &PathBuf) -> Result<(), String> {
+    /// The area of the drawing to save, and the size of the image it is saved to.
+    fn frame(&self) -> Result<(Rect, (u32, u32)), String> {
+        let (width, height) = (self.size.0 as f32, self.size.1 as f32);
+        let size = (self.size.0 as u32, self.size.1 as u32);
+        let canvas = Rect::from_xywh(0.0, 0.0, width, height).map(|view| (view, size));
+        let bounds = match (self.framing, self.drawing_bounds()) {
+            (Framing::Canvas, _) | (_, None) => return canvas.ok_or("invalid canvas size".into()),
+            (_, Some(bounds)) => bounds,
+        };
+        let frame = match self.framing {
+            Framing::Canvas => None,
+            Framing::Crop(margin) => {
+                let margin = margin as f32;
+                Rect::from_ltrb(
+                    (bounds.left() - margin).floor(),
+                    (bounds.top() - margin).floor(),
+                    (bounds.right() + margin).ceil(),
+                    (bounds.bottom() + margin).ceil(),
+                )
+                .filter(|view| view.width() >= 1.0 && view.height() >= 1.0)
+                .map(|view| (view, (view.width() as u32, view.height() as u32)))
+            }
+            Framing::Fit(margin) => {
+                let margin = 2.0 * margin as f32;
+                let scale =
+                    ((width - margin) / bounds.width()).min((height - margin) / bounds.height());
+                // the view keeps the shape of the canvas, centered on the drawing
+                let (view_width, view_height) = (width / scale, height / scale);
+                let (center_x, center_y) = (
+                    (bounds.left() + bounds.right()) / 2.0,
+                    (bounds.top() + bounds.bottom()) / 2.0,
+                );
+                Rect::from_xywh(
+                    center_x - view_width / 2.0,
+                    center_y - view_height / 2.0,
+                    view_width,
+                    view_height,
+                )
+                .filter(|_| scale.is_finite() && scale > 0.0)
+                .map(|view| (view, size))
+            }
+        };
+        frame.or(canvas).ok_or("invalid canvas size".into())
+    }
+
+    fn trace_json(&self) -> String {
+        let turtles = self
+            .turtles
+            .iter()
+            .map(|turtle| TurtleState {
+                x: turtle.x,
+                y: turtle.y,
+                heading: self.transform.motion(turtle.direction, 0).0,
+                pen_down: turtle.pen_status == PenStatus::Down,
+                color: turtle.color,
+                pen_size: turtle.pen_style.width,
+                visible: turtle.visible,
+            })
+            .collect::<Vec<_>>();
+        trace_json(
+            (self.size.0 as u32, self.size.1 as u32),
+            self.get_background(),
+            self.drawing_bounds(),
+            self.display_list().items(),
+            &turtles,
+        )
+    }
+
+    pub fn save(mut self, path: &PathBuf) -> Result<(), String> {
+        if self.animation.is_some() && path.extension().and_then(|s| s.to_str()) != Some("svg") {
+            return Err("only svg images can be animated".to_string());
+        }
+        // a fill left open is closed at the end of the program
+        if let Some(index) = self.turtles.iter().position(|t| t.fill_points.is_some()) {
+            self.current = index;
+            self.end_fill()?;
+        }
+        if path.extension().and_then(|s| s.to_str()) == Some("json") {
+            // turtles are written as their states rather than drawn
+            return std::fs::write(path, self.trace_json()).map_err(|e| e.to_string());
+        }
+        for index in 0..self.turtles.len() {
+            if self.turtles[index].visible {
+                self.current = index;
+                self.stamp()?;
+            }
+        }
         match path.extension().and_then(|s| s.to_str()) {
-            Some("svg") => self.output.save_svg(path),
-            Some("png") => self.output.save_png(path),
-            _ => {
-                return Err("file extension not supported".to_string());
+            Some("svg") => {
+                let (view, size) = self.frame()?;
+                let Some(animation) = self.animation else {
+                    return self.output.save_svg(path, view, size);
+                };
+                // drawn again to time every element, the canvas also rasterizes
+                // what it draws although only its svg elements are saved
+                let (width, height) = self.size;
+                let mut canvas =
+                    LogoCanvas::new(width as u32, height as u32)?.with_animation(animation);
+                self.display_list().replay(&mut canvas)?;
+                canvas.save_svg(path, view, size)
+            }
+            Some("png") if self.framing == Framing::Canvas => self.output.save_png(path),
+            Some("png") => {
+                // drawn again at the framed scale, so nothing off the canvas is cut off
+                let (view, (width, height)) = self.frame()?;
+                let scale = width as f32 / view.width();
+                let mut canvas = LogoCanvas::new(width, height)?.with_transform(
+                    PixelTransform::from_scale(scale, scale)
+                        .pre_translate(-view.left(), -view.top()),
+                );
+                self.display_list().replay(&mut canvas)?;
+                canvas.save_png(path)
             }
+            _ => Err("file extension not supported".to_string()),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum PenStatus {
     Up,
     Down,
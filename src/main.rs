@@ -1,17 +1,24 @@
 use std::process::exit;
-use clap::Parser;
+use clap::{ArgGroup, Parser};
+mod logo_canvas;
+mod logo_color;
+mod logo_display_list;
+mod logo_font;
 mod logo_interpreter;
+mod logo_json;
 mod logo_runner;
+#[cfg(test)]
 mod logo_interpreter_tests;
 mod r#const;
 
 /// A simple program to parse four arguments using clap.
 #[derive(Parser)]
+#[command(group(ArgGroup::new("framing").args(["fit", "crop"])))]
 struct Args {
     /// Path to a file
     file_path: std::path::PathBuf,
 
-    /// Path to an svg or png image
+    /// Path to an svg or png image, or a json trace of the drawing
     image_path: std::path::PathBuf,
 
     /// Height
@@ -19,6 +26,50 @@ struct Args {
 
     /// Width
     width: u32,
+
+    /// Report procedure redefinitions as warnings instead of errors
+    #[arg(long)]
+    warn_redefinitions: bool,
+
+    /// Match primitives, procedures and variables case-sensitively
+    #[arg(long)]
+    case_sensitive: bool,
+
+    /// Palette file remapping color indices, one `index r g b` or `index #rrggbb` per line
+    #[arg(long)]
+    palette: Option<std::path::PathBuf>,
+
+    /// Coordinates used by SETX, SETY, XCOR, YCOR and friends
+    #[arg(long, value_enum, default_value_t)]
+    coords: logo_runner::Coordinates,
+
+    /// Background color, a name, `#rrggbb`, `#rrggbbaa` or `transparent`
+    #[arg(long)]
+    background: Option<String>,
+
+    /// Scale and center the drawing to fill the image
+    #[arg(long, conflicts_with = "crop")]
+    fit: bool,
+
+    /// Shrink the image to the drawing
+    #[arg(long)]
+    crop: bool,
+
+    /// Space left around the drawing by --fit and --crop, in pixels
+    #[arg(long, default_value_t = 10, requires = "framing")]
+    margin: u32,
+
+    /// Animate the svg, drawing the lines in the order the program drew them
+    #[arg(long)]
+    animate: bool,
+
+    /// Pixels of line the animation draws per second
+    #[arg(long, default_value_t = 200.0, requires = "animate")]
+    speed: f32,
+
+    /// Show the turtles following the lines of the animation
+    #[arg(long, requires = "animate")]
+    animate_turtle: bool,
 }
 
 fn main() -> Result<(), ()> {
@@ -31,8 +82,59 @@ fn main() -> Result<(), ()> {
     let width = args.width;
     // read content from file_path
     let content = std::fs::read_to_string(&file_path).expect("Unable to read logo file");
-    let mut runner = logo_runner::LogoRunner::new(width, height);
-    let mut interpreter = logo_interpreter::LogoInterpreter::default(content);
+    let framing = if args.fit {
+        logo_runner::Framing::Fit(args.margin)
+    } else if args.crop {
+        logo_runner::Framing::Crop(args.margin)
+    } else {
+        logo_runner::Framing::Canvas
+    };
+    let mut runner = match logo_runner::LogoRunner::new(width, height) {
+        Ok(runner) => runner.with_coordinates(args.coords).with_framing(framing),
+        Err(e) => {
+            eprintln!("error incurred: {}", e);
+            exit(1)
+        }
+    };
+    if args.animate {
+        if image_path.extension().and_then(|s| s.to_str()) != Some("svg") {
+            eprintln!("error incurred: --animate needs an svg image path");
+            exit(1)
+        }
+        if args.speed <= 0.0 || !args.speed.is_finite() {
+            eprintln!("error incurred: animation speed must be positive");
+            exit(1)
+        }
+        runner = runner.with_animation(logo_canvas::Animation {
+            speed: args.speed,
+            turtle: args.animate_turtle,
+        });
+    }
+    if let Some(background) = args.background {
+        let background = logo_color::LogoColor::from_word(&background)
+            .and_then(|color| runner.set_background(color));
+        if let Err(e) = background {
+            eprintln!("error incurred: {}", e);
+            exit(1)
+        }
+    }
+    if let Some(palette_path) = args.palette {
+        let palette = std::fs::read_to_string(&palette_path).expect("Unable to read palette file");
+        let entries = logo_color::parse_palette(&palette).and_then(|entries| {
+            entries
+                .into_iter()
+                .try_for_each(|(index, color)| runner.set_palette(index, color))
+        });
+        if let Err(e) = entries {
+            eprintln!("error incurred: {}", e);
+            exit(1)
+        }
+    }
+    let options = logo_interpreter::LogoOptions {
+        warn_redefinitions: args.warn_redefinitions,
+        case_sensitive: args.case_sensitive,
+    };
+    let mut interpreter = logo_interpreter::LogoInterpreter::default(content).with_options(options);
     match interpreter.interpret(&mut runner) {
         Err(e) => {
             eprintln!("error incurred: {}", e);